@@ -0,0 +1,241 @@
+// Changes of coordinate systems are animated, so that it's easier to see how one convention maps to another.
+// Only the displayed transforms are animated, values of objects change immediately
+
+use std::f32::consts::PI;
+
+use bevy::{math::Affine3A, prelude::*, transform::TransformSystem};
+
+use crate::geometry::{self, Axis, CoordinateSystem, CoordinateSystemChanged, ObjectMovedByCoordinates};
+
+/// seconds
+const DURATION: f32 = 0.8;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CoordinateAnimation>()
+            .add_systems(
+                Update,
+                (system_start_animation, system_animate_axes)
+                    .chain()
+                    .after(geometry::GeometrySet),
+            )
+            .add_systems(
+                PostUpdate,
+                system_animate_objects.after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct CoordinateAnimation {
+    pub enabled: bool,
+    /// show the plane that the axes are mirrored through when the handedness changes
+    pub show_mirror_plane: bool,
+    progress: f32,
+    axes: Option<AxesTransition>,
+    /// transforms that objects moved from
+    objects: Vec<(Entity, Transform)>,
+}
+
+impl Default for CoordinateAnimation {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            show_mirror_plane: true,
+            progress: 1.0,
+            axes: None,
+            objects: Vec::new(),
+        }
+    }
+}
+
+impl CoordinateAnimation {
+    fn eased_progress(&self) -> f32 {
+        let t = self.progress;
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// Change of the axes split into a reflection, if the handedness changes, followed by a rotation
+struct AxesTransition {
+    previous: Mat3,
+    rotation: Quat,
+    mirror_normal: Option<Vec3>,
+}
+
+impl AxesTransition {
+    fn new(previous: Mat3, current: Mat3) -> Self {
+        let relative = current * previous.transpose();
+        if relative.determinant() > 0.0 {
+            return Self { previous, rotation: Quat::from_mat3(&relative), mirror_normal: None };
+        }
+
+        // the negated matrix rotates by some angle around n,
+        // which is the same as mirroring through the plane with normal n and rotating by that angle plus PI
+        let (axis, angle) = Quat::from_mat3(&-relative).to_axis_angle();
+        Self {
+            previous,
+            rotation: Quat::from_axis_angle(axis, angle + PI),
+            mirror_normal: Some(axis),
+        }
+    }
+
+    /// Axes at time `t`, the mirrored axes shrink to zero and grow in the opposite direction
+    fn axes(&self, t: f32) -> Mat3 {
+        let mirror = self.mirror_normal.map_or(Mat3::IDENTITY, |n| {
+            Mat3::IDENTITY - Mat3::from_cols(n * n.x, n * n.y, n * n.z) * (2.0 * t)
+        });
+        Mat3::from_quat(Quat::IDENTITY.slerp(self.rotation, t)) * mirror * self.previous
+    }
+}
+
+fn system_start_animation(
+    mut animation: ResMut<CoordinateAnimation>,
+    mut coord_events: EventReader<CoordinateSystemChanged>,
+    mut moved_events: EventReader<ObjectMovedByCoordinates>,
+    coord_q: Query<&CoordinateSystem>,
+) {
+    let changed = coord_events.read().last();
+    let moved: Vec<_> = moved_events.read().map(|event| (event.ent, event.previous)).collect();
+    if !animation.enabled || (changed.is_none() && moved.is_empty()) {
+        return;
+    }
+
+    // objects that move on their own keep the axes transition, so that it still finishes at the current axes
+    if let Some(event) = changed {
+        let coord = coord_q.single().unwrap();
+        animation.axes = Some(AxesTransition::new(event.previous, coord.user2internal));
+    }
+    animation.objects = moved;
+    animation.progress = 0.0;
+}
+
+fn system_animate_axes(
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+    mut animation: ResMut<CoordinateAnimation>,
+    mut axes_q: Query<(&mut Transform, &Axis)>,
+) {
+    if animation.progress >= 1.0 {
+        return;
+    }
+    animation.progress = (animation.progress + time.delta_secs() / DURATION).min(1.0);
+    let t = animation.eased_progress();
+
+    let Some(transition) = &animation.axes else {
+        return;
+    };
+
+    let axes = transition.axes(t);
+    for (mut tf, axis) in axes_q.iter_mut() {
+        let direction = axes * axis.to_vec();
+        let length = direction.length();
+        tf.rotation = Quat::from_rotation_arc(axis.to_vec(), direction.normalize_or(axis.to_vec()));
+        tf.scale = Vec3::ONE + axis.to_vec() * (length.max(0.001) - 1.0);
+    }
+
+    if animation.show_mirror_plane
+        && let Some(normal) = transition.mirror_normal
+    {
+        let isometry = Isometry3d::new(Vec3::ZERO, Quat::from_rotation_arc(Vec3::Z, normal));
+        let color = bevy::color::palettes::css::DARK_VIOLET;
+        gizmos.grid(isometry, UVec2::splat(8), Vec2::splat(0.25), color).outer_edges();
+    }
+}
+
+/// Objects are displayed between the transforms they moved from and their current ones.
+/// Global transforms are overwritten after propagation, so the transforms themselves are left alone
+fn system_animate_objects(
+    mut animation: ResMut<CoordinateAnimation>,
+    tf_q: Query<&Transform>,
+    children_q: Query<&Children>,
+    mut global_q: Query<&mut GlobalTransform>,
+) {
+    if animation.objects.is_empty() {
+        return;
+    }
+    let t = animation.eased_progress();
+
+    for &(ent, previous) in &animation.objects {
+        let Ok(current) = tf_q.get(ent) else {
+            continue;
+        };
+        let displayed = Transform {
+            translation: previous.translation.lerp(current.translation, t),
+            rotation: previous.rotation.slerp(current.rotation, t),
+            scale: previous.scale.lerp(current.scale, t),
+        };
+        override_global_transforms(ent, displayed.compute_affine(), &tf_q, &children_q, &mut global_q);
+    }
+
+    if animation.progress >= 1.0 {
+        animation.objects.clear();
+    }
+}
+
+fn override_global_transforms(
+    ent: Entity,
+    affine: Affine3A,
+    tf_q: &Query<&Transform>,
+    children_q: &Query<&Children>,
+    global_q: &mut Query<&mut GlobalTransform>,
+) {
+    if let Ok(mut global) = global_q.get_mut(ent) {
+        *global = GlobalTransform::from(affine);
+    }
+    for &child in children_q.get(ent).into_iter().flatten() {
+        let local = tf_q.get(child).map_or(Affine3A::IDENTITY, Transform::compute_affine);
+        override_global_transforms(child, affine * local, tf_q, children_q, global_q);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::gizmos::gizmos::GizmoStorage;
+
+    use super::*;
+    use crate::geometry::Hand;
+
+    #[test]
+    fn moved_objects_keep_axes_animation() {
+        let mut gizmo_config = GizmoConfigStore::default();
+        gizmo_config.insert(GizmoConfig::default(), DefaultGizmoConfigGroup);
+
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .insert_resource(gizmo_config)
+            .init_resource::<GizmoStorage<DefaultGizmoConfigGroup, ()>>()
+            .init_resource::<CoordinateAnimation>()
+            .add_event::<CoordinateSystemChanged>()
+            .add_event::<ObjectMovedByCoordinates>()
+            .add_systems(Update, (system_start_animation, system_animate_axes).chain());
+
+        let coord = CoordinateSystem::new(Vec3::Z, Vec3::Y, Hand::Left, None, default(), 1.0);
+        let user2internal = coord.user2internal;
+        app.world_mut().spawn(coord);
+        let axes: Vec<_> = Axis::all()
+            .into_iter()
+            .map(|axis| app.world_mut().spawn((Transform::default(), axis)).id())
+            .collect();
+
+        // interrupted halfway by an object that only moved
+        app.world_mut().send_event(CoordinateSystemChanged { previous: Mat3::IDENTITY });
+        app.world_mut().resource_mut::<Time>().advance_by(Duration::from_secs_f32(DURATION / 2.0));
+        app.update();
+        let ent = app.world_mut().spawn(Transform::default()).id();
+        app.world_mut().send_event(ObjectMovedByCoordinates { ent, previous: Transform::default() });
+        app.world_mut().resource_mut::<Time>().advance_by(Duration::from_secs_f32(DURATION * 2.0));
+        app.update();
+
+        for ent in axes {
+            let (tf, axis) = app.world_mut().query::<(&Transform, &Axis)>().get(app.world(), ent).unwrap();
+            let expected = user2internal * axis.to_vec();
+            assert!((tf.rotation * axis.to_vec()).abs_diff_eq(expected, 1e-4), "{} axis", axis.name());
+            assert!(tf.scale.abs_diff_eq(Vec3::ONE, 1e-4), "{} axis is scaled by {}", axis.name(), tf.scale);
+        }
+    }
+}
@@ -76,7 +76,7 @@ pub fn pan_orbit_camera(
             //let yaw = Quat::from_rotation_y(-delta_x);
             //let pitch = Quat::from_rotation_x(-delta_y);
             transform.rotation = yaw * transform.rotation; // rotate around global y axis
-            transform.rotation = transform.rotation * pitch; // rotate around local x axis
+            transform.rotation *= pitch; // rotate around local x axis
         }
         //else if scroll.abs() > 0.0 {
         //any = true;
@@ -102,6 +102,5 @@ pub fn pan_orbit_camera(
 
 fn get_primary_window_size(windows: &Query<&Window, With<PrimaryWindow>>) -> Vec2 {
     let window = windows.single().unwrap();
-    let window = Vec2::new(window.width() as f32, window.height() as f32);
-    window
+    Vec2::new(window.width(), window.height())
 }
@@ -2,6 +2,8 @@
 
 use bevy::prelude::*;
 
+/// Order of the components, written the way it's usually spelled
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum QuatStrMode {
     XYZW,
@@ -103,7 +105,7 @@ where
 
 fn parse_strings_to_f32<const S: usize>(strings: &[String; S]) -> [f32; S] {
     let mut parsed = [0.0; S];
-    for (from, to) in strings.into_iter().zip(&mut parsed) {
+    for (from, to) in strings.iter().zip(&mut parsed) {
         *to = from.parse::<f32>().unwrap_or_default();
     }
 
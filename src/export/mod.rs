@@ -0,0 +1,394 @@
+// glTF uses the same conventions as the internal coordinate system (Y up, right-handed),
+// so internal transforms are exported as is
+
+use std::collections::HashMap;
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+};
+use bevy_rich_text3d::Text3d;
+use serde_json::{Value, json};
+
+use crate::{
+    arrow::BodyMaterial,
+    group::GroupedObjects,
+    mesh::Outline,
+    repr::ComputedRepresentation,
+    ui::ConfigIO,
+};
+
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExportStatus>()
+            .add_event::<ExportGltfCommand>()
+            .add_systems(Update, system_export_gltf);
+    }
+}
+
+/// Exports all objects as a binary glTF file.
+/// On the web the file is downloaded instead, using the file name from `path`
+#[derive(Event)]
+pub struct ExportGltfCommand {
+    pub path: String,
+}
+
+/// Result of the last export, displayed in the settings
+#[derive(Resource, Default)]
+pub struct ExportStatus {
+    pub message: Option<Result<String, String>>,
+}
+
+type ExportedObject = (
+    Option<&'static Name>,
+    Option<&'static Transform>,
+    Option<&'static GroupedObjects>,
+    Option<&'static Children>,
+    &'static ComputedRepresentation,
+    Option<&'static BodyMaterial>,
+);
+
+type ExportedDisplayNode = (
+    &'static Transform,
+    Option<&'static Mesh3d>,
+    Option<&'static MeshMaterial3d<StandardMaterial>>,
+    Option<&'static Children>,
+);
+
+#[derive(SystemParam)]
+struct SceneQueries<'w, 's> {
+    objects_q: Query<'w, 's, ExportedObject>,
+    display_q: Query<'w, 's, ExportedDisplayNode, (Without<Outline>, Without<Text3d>)>,
+    meshes: Res<'w, Assets<Mesh>>,
+    materials: Res<'w, Assets<StandardMaterial>>,
+}
+
+fn system_export_gltf(
+    mut events: EventReader<ExportGltfCommand>,
+    mut status: ResMut<ExportStatus>,
+    config_q: Query<Entity, With<ConfigIO>>,
+    scene: SceneQueries,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+
+    let config_ent = config_q.single().unwrap();
+    let solo_active = scene.objects_q.iter().any(|(.., computed, _)| computed.solo);
+    let mut builder = GltfBuilder::new(&scene, solo_active);
+
+    let roots = scene
+        .objects_q
+        .get(config_ent)
+        .ok()
+        .and_then(|(_, _, grouped, ..)| grouped)
+        .into_iter()
+        .flatten()
+        .filter_map(|ent| builder.add_object(ent))
+        .collect();
+
+    let bytes = builder.finish(roots);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let result = std::fs::write(&event.path, bytes)
+        .map(|_| format!("Exported to {}", event.path))
+        .map_err(|err| format!("{}: {err}", event.path));
+
+    #[cfg(target_arch = "wasm32")]
+    let result = web::download(&event.path, &bytes).map(|_| format!("Downloaded {}", event.path));
+
+    status.message = Some(result);
+}
+
+struct GltfBuilder<'a, 'w, 's> {
+    scene: &'a SceneQueries<'w, 's>,
+    solo_active: bool,
+
+    nodes: Vec<Value>,
+    meshes: Vec<Value>,
+    materials: Vec<Value>,
+    accessors: Vec<Value>,
+    buffer_views: Vec<Value>,
+    buffer: Vec<u8>,
+
+    mesh_cache: HashMap<(AssetId<Mesh>, usize), usize>,
+    material_cache: HashMap<AssetId<StandardMaterial>, usize>,
+}
+
+impl<'a, 'w, 's> GltfBuilder<'a, 'w, 's> {
+    fn new(scene: &'a SceneQueries<'w, 's>, solo_active: bool) -> Self {
+        Self {
+            scene,
+            solo_active,
+            nodes: default(),
+            meshes: default(),
+            materials: default(),
+            accessors: default(),
+            buffer_views: default(),
+            buffer: default(),
+            mesh_cache: default(),
+            material_cache: default(),
+        }
+    }
+
+    /// Adds an arrow or a group with everything in it, returns the index of its node.
+    /// Hidden objects are not exported
+    fn add_object(&mut self, ent: Entity) -> Option<usize> {
+        let (name, tf, grouped, children, computed, body_material) = self.scene.objects_q.get(ent).ok()?;
+        if !computed.is_shown(self.solo_active) {
+            return None;
+        }
+        // objects spawned this frame don't have a name yet
+        let name = name.map_or("", Name::as_str);
+
+        let mut node_children: Vec<usize> = grouped
+            .into_iter()
+            .flatten()
+            .filter_map(|child| self.add_object(child))
+            .collect();
+
+        // groups only contain other objects, arrows also have the displayed shape as children
+        if let Some(children) = children {
+            let body = body_material.map(|body_material| {
+                (body_material.0.id(), self.add_material(name, computed.color.with_alpha(computed.opacity)))
+            });
+            node_children.extend(
+                children
+                    .iter()
+                    .filter_map(|child| self.add_display_node(child, body)),
+            );
+        }
+
+        let mut node = json!({ "name": name });
+        if let Some(tf) = tf {
+            write_transform(&mut node, tf);
+        }
+        if !node_children.is_empty() {
+            node["children"] = json!(node_children);
+        }
+
+        self.nodes.push(node);
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Meshes using the `body` material get the object's color instead
+    fn add_display_node(
+        &mut self,
+        ent: Entity,
+        body: Option<(AssetId<StandardMaterial>, usize)>,
+    ) -> Option<usize> {
+        let (tf, mesh, material, children) = self.scene.display_q.get(ent).ok()?;
+
+        let mut node = json!({});
+        write_transform(&mut node, tf);
+
+        if let (Some(mesh), Some(material)) = (mesh, material) {
+            let material = match body {
+                Some((body_id, body_index)) if body_id == material.id() => body_index,
+                _ => self.add_standard_material(material.id()),
+            };
+            if let Some(mesh) = self.add_mesh(mesh.id(), material) {
+                node["mesh"] = json!(mesh);
+            }
+        }
+
+        let node_children: Vec<usize> = children
+            .into_iter()
+            .flatten()
+            .filter_map(|child| self.add_display_node(*child, body))
+            .collect();
+        if !node_children.is_empty() {
+            node["children"] = json!(node_children);
+        }
+
+        self.nodes.push(node);
+        Some(self.nodes.len() - 1)
+    }
+
+    fn add_material(&mut self, name: &str, color: LinearRgba) -> usize {
+        let mut material = json!({
+            "name": name,
+            "pbrMetallicRoughness": {
+                "baseColorFactor": color.to_f32_array(),
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+            "doubleSided": true,
+            // everything in the scene is unlit
+            "extensions": { "KHR_materials_unlit": {} },
+        });
+        if color.alpha < 1.0 {
+            material["alphaMode"] = json!("BLEND");
+        }
+
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    fn add_standard_material(&mut self, id: AssetId<StandardMaterial>) -> usize {
+        if let Some(index) = self.material_cache.get(&id) {
+            return *index;
+        }
+
+        let color = self
+            .scene
+            .materials
+            .get(id)
+            .map_or(LinearRgba::WHITE, |material| material.base_color.into());
+        let index = self.add_material("", color);
+        self.material_cache.insert(id, index);
+        index
+    }
+
+    fn add_mesh(&mut self, id: AssetId<Mesh>, material: usize) -> Option<usize> {
+        if let Some(index) = self.mesh_cache.get(&(id, material)) {
+            return Some(*index);
+        }
+
+        let mesh = self.scene.meshes.get(id)?;
+        let mode = match mesh.primitive_topology() {
+            PrimitiveTopology::LineList => 1,
+            PrimitiveTopology::TriangleList => 4,
+            _ => return None,
+        };
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return None;
+        };
+
+        let (min, max) = positions.iter().fold(
+            (Vec3::INFINITY, Vec3::NEG_INFINITY),
+            |(min, max), pos| (min.min(Vec3::from(*pos)), max.max(Vec3::from(*pos))),
+        );
+        let mut attributes = json!({
+            "POSITION": self.add_accessor(
+                bytes_of_vec3(positions),
+                positions.len(),
+                json!({ "componentType": 5126, "type": "VEC3", "min": min.to_array(), "max": max.to_array() }),
+                34962,
+            ),
+        });
+        if let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            attributes["NORMAL"] = json!(self.add_accessor(
+                bytes_of_vec3(normals),
+                normals.len(),
+                json!({ "componentType": 5126, "type": "VEC3" }),
+                34962,
+            ));
+        }
+
+        // shading of models is baked into vertex colors
+        if let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+            attributes["COLOR_0"] = json!(self.add_accessor(
+                colors.iter().flatten().flat_map(|v| v.to_le_bytes()).collect(),
+                colors.len(),
+                json!({ "componentType": 5126, "type": "VEC4" }),
+                34962,
+            ));
+        }
+
+        let mut primitive = json!({ "attributes": attributes, "mode": mode, "material": material });
+        if let Some(indices) = mesh.indices() {
+            let bytes: Vec<u8> = match indices {
+                Indices::U16(indices) => indices.iter().flat_map(|i| (*i as u32).to_le_bytes()).collect(),
+                Indices::U32(indices) => indices.iter().flat_map(|i| i.to_le_bytes()).collect(),
+            };
+            primitive["indices"] = json!(self.add_accessor(
+                bytes,
+                indices.len(),
+                json!({ "componentType": 5125, "type": "SCALAR" }),
+                34963,
+            ));
+        }
+
+        self.meshes.push(json!({ "primitives": [primitive] }));
+        let index = self.meshes.len() - 1;
+        self.mesh_cache.insert((id, material), index);
+        Some(index)
+    }
+
+    /// `accessor` should contain the component type and the type, the rest is filled in here
+    fn add_accessor(&mut self, bytes: Vec<u8>, count: usize, mut accessor: Value, target: u32) -> usize {
+        // accessors of all used types must be aligned to 4 bytes
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        self.buffer.extend(bytes);
+
+        accessor["bufferView"] = json!(self.buffer_views.len() - 1);
+        accessor["count"] = json!(count);
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    /// Returns contents of a .glb file
+    fn finish(mut self, roots: Vec<usize>) -> Vec<u8> {
+        let mut root = json!({
+            "asset": { "version": "2.0", "generator": "quaternions-offline" },
+            "scene": 0,
+            "scenes": [{ "nodes": roots }],
+        });
+
+        // glTF doesn't allow empty arrays
+        for (key, values) in [
+            ("nodes", self.nodes),
+            ("meshes", self.meshes),
+            ("materials", self.materials),
+            ("accessors", self.accessors),
+            ("bufferViews", self.buffer_views),
+        ] {
+            if !values.is_empty() {
+                root[key] = Value::Array(values);
+            }
+        }
+        if root.get("materials").is_some() {
+            root["extensionsUsed"] = json!(["KHR_materials_unlit"]);
+        }
+        if !self.buffer.is_empty() {
+            root["buffers"] = json!([{ "byteLength": self.buffer.len() }]);
+        }
+
+        let mut json = serde_json::to_vec(&root).unwrap();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+
+        let mut chunks = vec![(*b"JSON", json)];
+        if !self.buffer.is_empty() {
+            chunks.push((*b"BIN\0", self.buffer));
+        }
+
+        let total_len = 12 + chunks.iter().map(|(_, data)| 8 + data.len()).sum::<usize>();
+        let mut glb = Vec::with_capacity(total_len);
+        glb.extend(b"glTF");
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((total_len as u32).to_le_bytes());
+        for (kind, data) in chunks {
+            glb.extend((data.len() as u32).to_le_bytes());
+            glb.extend(kind);
+            glb.extend(data);
+        }
+
+        glb
+    }
+}
+
+fn write_transform(node: &mut Value, tf: &Transform) {
+    node["translation"] = json!(tf.translation.to_array());
+    // glTF requires unit quaternions, rotations applied without normalization can violate that
+    node["rotation"] = json!(tf.rotation.normalize().to_array());
+    node["scale"] = json!(tf.scale.to_array());
+}
+
+fn bytes_of_vec3(values: &[[f32; 3]]) -> Vec<u8> {
+    values.iter().flatten().flat_map(|v| v.to_le_bytes()).collect()
+}
@@ -0,0 +1,24 @@
+use wasm_bindgen::JsCast;
+
+/// Makes the browser download `bytes` as a file
+pub fn download(file_name: &str, bytes: &[u8]) -> Result<(), String> {
+    let to_string = |err: wasm_bindgen::JsValue| format!("{err:?}");
+
+    let array = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type("model/gltf-binary");
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&array, &options).map_err(to_string)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(to_string)?;
+
+    let document = web_sys::window().unwrap().document().unwrap();
+    let anchor: web_sys::HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(to_string)?
+        .dyn_into()
+        .map_err(|_| "failed to create a link".to_string())?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    web_sys::Url::revoke_object_url(&url).map_err(to_string)
+}
@@ -1,8 +1,9 @@
 // this file is somewhat complicated, especially due to rotated positions support
 // TODO: refactor?
 
+use crate::repr::{ComputedRepresentation, Coordinates};
 use crate::ui;
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 
 pub struct GeometryPlugin;
 
@@ -16,14 +17,35 @@ impl Plugin for GeometryPlugin {
                 system_process_transform_commands,
                 system_sync_objects,
                 system_sync_coordinates,
+                system_sync_object_coordinates,
             )
                 .chain()
+                .in_set(GeometrySet)
                 .after(ui::UiSet),
         )
-        .add_event::<ApplyTransformCommand>();
+        .add_event::<ApplyTransformCommand>()
+        .add_event::<CoordinateSystemChanged>()
+        .add_event::<ObjectMovedByCoordinates>();
     }
 }
 
+/// The main coordinate system changed, `previous` is its old user to internal matrix
+#[derive(Event)]
+pub struct CoordinateSystemChanged {
+    pub previous: Mat3,
+}
+
+/// Numbers of the object were kept when its coordinate system changed, so it moved from `previous`
+#[derive(Event)]
+pub struct ObjectMovedByCoordinates {
+    pub ent: Entity,
+    pub previous: Transform,
+}
+
+/// Systems that convert between user and internal coordinates
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GeometrySet;
+
 #[derive(Component)]
 pub struct MainPlane;
 
@@ -43,9 +65,18 @@ impl Axis {
         }
     }
 
-    // pub fn all() -> [Self; 3] {
-    //     [Axis::X, Axis::Y, Axis::Z]
-    // }
+    pub fn all() -> [Self; 3] {
+        [Axis::X, Axis::Y, Axis::Z]
+    }
+
+    pub fn color(self) -> Color {
+        use bevy::color::palettes::css as pallette;
+        match self {
+            Axis::X => pallette::RED.into(),
+            Axis::Y => pallette::GREEN.into(),
+            Axis::Z => pallette::BLUE.into(),
+        }
+    }
 
     pub fn name(&self) -> &'static str {
         match self {
@@ -69,7 +100,7 @@ pub enum PositionMode {
     Rotated,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, PartialEq)]
 pub struct CoordinateSystem {
     pub user2internal: Mat3,
     pub internal2user: Mat3,
@@ -88,6 +119,77 @@ impl Default for CoordinateSystem {
     }
 }
 
+impl CoordinateSystem {
+    /// `custom_basis` is relative to the convention given by the directions and the hand
+    pub fn new(
+        up_direction: Vec3,
+        forward_direction: Vec3,
+        hand: Hand,
+        custom_basis: Option<Mat3>,
+        position_mode: PositionMode,
+        positions_scale: f32,
+    ) -> Self {
+        let mut user2internal = user2internal_basis(up_direction, forward_direction, hand);
+        if let Some(basis) = custom_basis {
+            user2internal *= basis;
+        }
+
+        Self {
+            user2internal,
+            internal2user: user2internal.transpose(),
+            position_mode,
+            positions_scale,
+        }
+    }
+}
+
+/// Coordinate system of an object, the main one unless one of its groups overrides it
+#[derive(Component, Default, Deref)]
+pub struct ObjectCoordinates(pub CoordinateSystem);
+
+/// Main coordinate system and the extra ones, that groups can choose from
+pub struct CoordinateSystems<'a> {
+    pub main: &'a CoordinateSystem,
+    pub extra: &'a ui::ExtraCoordinateSystems,
+}
+
+/// Main and named coordinate systems, as a system parameter
+#[derive(SystemParam)]
+pub struct CoordinateSystemsParam<'w, 's> {
+    main_q: Query<'w, 's, &'static CoordinateSystem>,
+    extra_q: Query<'w, 's, &'static ui::ExtraCoordinateSystems>,
+}
+
+impl CoordinateSystemsParam<'_, '_> {
+    pub fn get(&self) -> CoordinateSystems<'_> {
+        CoordinateSystems { main: self.main_q.single().unwrap(), extra: self.extra_q.single().unwrap() }
+    }
+}
+
+impl CoordinateSystems<'_> {
+    pub fn resolve(&self, coordinates: &Coordinates) -> CoordinateSystem {
+        match coordinates {
+            Coordinates::Main => self.main.clone(),
+            Coordinates::Named(id) => self
+                .extra
+                .get(*id)
+                .map_or_else(|| self.main.clone(), |coords| coords.coordinate_system()),
+        }
+    }
+}
+
+/// Matrix that converts vectors from a convention with the given up and forward directions
+/// into the internal coordinate system (Y up, -Z forward, right-handed)
+pub fn user2internal_basis(up_direction: Vec3, forward_direction: Vec3, hand: Hand) -> Mat3 {
+    let side_direction =
+    forward_direction.cross(up_direction) * if hand == Hand::Left { -1.0 } else { 1.0 };
+
+    let to_internal_basis = Mat3::from_cols(Vec3::X, Vec3::Y, Vec3::NEG_Z);
+    let to_user_basis = Mat3::from_cols(side_direction, up_direction, forward_direction);
+
+    to_internal_basis * to_user_basis.transpose()
+}
+
 pub fn convert_rotation(mat: &Mat3, mut quat: Quat) -> Quat {
     let converted = *mat * quat.xyz();
     quat.x = converted.x;
@@ -125,11 +227,97 @@ pub fn convert_position_i2u(
     *mat * pos * scale
 }
 
+/// Scale axes are remapped like directions. Sign flips of the axes cancel out,
+/// so a negative scale stays a reflection along the same axis in both coordinate systems.
+/// Custom bases that aren't axis-aligned can't be represented exactly, the scale is projected onto the new axes
+pub fn convert_scale(mat: &Mat3, scale: Vec3) -> Vec3 {
+    let row = |i: usize| mat.row(i) * mat.row(i);
+    Vec3::new(row(0).dot(scale), row(1).dot(scale), row(2).dot(scale))
+}
+
+/// Parts of a transform matrix `T * R * H * S`, where `H` is the shear.
+/// Only translation, rotation and scale can be represented by objects
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TransformDecomposition {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    /// negative X scale if the matrix flips handedness
+    pub scale: Vec3,
+    /// XY, XZ and YZ factors of the upper triangle of `H`
+    pub shear: Vec3,
+    /// bottom row of the matrix, (0, 0, 0, 1) for affine transforms
+    pub projective: Vec4,
+}
+
+impl TransformDecomposition {
+    /// `None` if one of the axes is scaled to zero
+    pub fn new(mat: Mat4) -> Option<Self> {
+        let [c0, c1, c2] = [mat.x_axis.xyz(), mat.y_axis.xyz(), mat.z_axis.xyz()];
+
+        let mut scale = Vec3::ZERO;
+        let mut shear = Vec3::ZERO;
+
+        scale.x = c0.length();
+        let mut r0 = c0.try_normalize()?;
+
+        shear.x = r0.dot(c1);
+        let c1 = c1 - r0 * shear.x;
+        scale.y = c1.length();
+        let r1 = c1.try_normalize()?;
+
+        shear.y = r0.dot(c2);
+        shear.z = r1.dot(c2);
+        let c2 = c2 - r0 * shear.y - r1 * shear.z;
+        scale.z = c2.length();
+        let r2 = c2.try_normalize()?;
+
+        shear.x /= scale.y;
+        shear.y /= scale.z;
+        shear.z /= scale.z;
+
+        if Mat3::from_cols(r0, r1, r2).determinant() < 0.0 {
+            r0 = -r0;
+            scale.x = -scale.x;
+            shear.x = -shear.x;
+            shear.y = -shear.y;
+        }
+
+        Some(TransformDecomposition {
+            translation: mat.w_axis.xyz(),
+            rotation: Quat::from_mat3(&Mat3::from_cols(r0, r1, r2)),
+            scale,
+            shear,
+            projective: mat.row(3),
+        })
+    }
+
+    pub fn to_matrix(self) -> Mat4 {
+        let shear = Mat3::from_cols(Vec3::X, Vec3::new(self.shear.x, 1.0, 0.0), self.shear.yz().extend(1.0));
+        let linear = Mat3::from_quat(self.rotation) * shear * Mat3::from_diagonal(self.scale);
+
+        let mut mat = Mat4::from_mat3(linear);
+        mat.w_axis = self.translation.extend(1.0);
+        // the projective row replaces the bottom row, so that each of the parts can be edited separately
+        for (i, value) in self.projective.to_array().into_iter().enumerate() {
+            mat.col_mut(i)[3] = value;
+        }
+        mat
+    }
+
+    pub fn has_shear(self) -> bool {
+        !self.shear.abs_diff_eq(Vec3::ZERO, 1e-4)
+    }
+
+    pub fn is_projective(self) -> bool {
+        !self.projective.abs_diff_eq(Vec4::W, 1e-4)
+    }
+}
+
 fn system_sync_coordinates(
     config_q: Query<Ref<ui::ConfigIO>>,
     mut coord_q: Query<&mut CoordinateSystem>,
     mut axes_q: Query<(&mut Transform, &Axis), Without<crate::arrow::Arrow>>,
-    mut arrows_q: Query<(&mut Transform, &UserTransform), With<crate::arrow::Arrow>>,
+    mut events: EventWriter<CoordinateSystemChanged>,
 ) {
     let mut coord = coord_q.single_mut().unwrap();
     let config = config_q.single().unwrap();
@@ -139,29 +327,76 @@ fn system_sync_coordinates(
 
     let forward_direction = config.forward.to_vec() * config.forward_sign;
     let up_direction = config.up.to_vec() * config.up_sign;
-    let side_direction =
-    forward_direction.cross(up_direction) * if config.hand == Hand::Left { -1.0 } else { 1.0 };
-
-    let to_internal_basis = Mat3::from_cols(Vec3::X, Vec3::Y, Vec3::NEG_Z);
-    let to_user_basis = Mat3::from_cols(side_direction, up_direction, forward_direction);
 
-    coord.positions_scale = config.positions_scale;
-    coord.position_mode = config.position_mode;
-    coord.user2internal = to_internal_basis * to_user_basis.transpose();
-    coord.internal2user = coord.user2internal.transpose();
+    let previous = coord.user2internal;
+    *coord = CoordinateSystem::new(
+        up_direction,
+        forward_direction,
+        config.hand,
+        config.valid_custom_basis(),
+        config.position_mode,
+        config.positions_scale,
+    );
 
     for (mut tf, axis) in axes_q.iter_mut() {
         let axis = axis.to_vec();
         tf.rotation = Quat::from_rotation_arc(axis, coord.user2internal * axis);
+        // an animation that is still running may have scaled the axis
+        tf.scale = Vec3::ONE;
+    }
+
+    if coord.user2internal != previous {
+        events.write(CoordinateSystemChanged { previous });
     }
+}
 
+type ArrowCoordinatesQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        Ref<'static, ComputedRepresentation>,
+        Mut<'static, ObjectCoordinates>,
+        &'static mut Transform,
+        &'static UserTransform,
+    ),
+    With<crate::arrow::Arrow>,
+>;
+
+/// Resolves the coordinate system of each object after it, or the coordinate system chosen by its group, changes
+fn system_sync_object_coordinates(
+    config_q: Query<(&ui::ConfigIO, Ref<ui::ExtraCoordinateSystems>)>,
+    coord_q: Query<Ref<CoordinateSystem>>,
+    mut arrows_q: ArrowCoordinatesQuery,
+    mut events: EventWriter<ObjectMovedByCoordinates>,
+) {
+    let (config, extra) = config_q.single().unwrap();
+    let main = coord_q.single().unwrap();
+    let systems = CoordinateSystems { main: &main, extra: &extra };
 
-    if config.keep_numbers {
-        for (mut tf, utf) in arrows_q.iter_mut() {
+    for (ent, computed, mut coords, mut tf, utf) in arrows_q.iter_mut() {
+        if !main.is_changed() && !extra.is_changed() && !computed.is_changed() && !coords.is_added() {
+            continue;
+        }
+
+        let coord = systems.resolve(&computed.coordinates);
+        if coord == coords.0 {
+            continue;
+        }
+        // values of new objects are already in the right coordinate system
+        let is_new = coords.is_added();
+        coords.0 = coord;
+
+        // only objects that keep their numbers move in the scene, with keep_numbers off their numbers
+        // are converted instead and they stay in place, so there's nothing to animate for them
+        if config.keep_numbers && !is_new {
+            let previous = *tf;
             // let num_rot = convert_rotation(&prev_internal2user, tf.rotation);
             // let num_pos = convert_position(&prev_internal2user, prev_scale.recip(), prev_pos_mode, num_rot.inverse(), tf.translation);
-            tf.rotation = convert_rotation(&coord.user2internal, utf.rotation);
-            tf.translation = convert_position_u2i(&coord.user2internal, coord.positions_scale, coord.position_mode, tf.rotation, utf.translation);
+            tf.rotation = convert_rotation(&coords.user2internal, utf.rotation);
+            tf.translation = convert_position_u2i(&coords.user2internal, coords.positions_scale, coords.position_mode, tf.rotation, utf.translation);
+            tf.scale = convert_scale(&coords.user2internal, utf.scale);
+            events.write(ObjectMovedByCoordinates { ent, previous });
         }
     }
 }
@@ -169,13 +404,8 @@ fn system_sync_coordinates(
 #[derive(Component, Default, Deref, DerefMut)]
 pub struct UserTransform(pub Transform);
 
-fn system_sync_objects(
-    coord_q: Query<Ref<CoordinateSystem>>,
-    mut arrows_q: Query<(Ref<Transform>, &mut UserTransform)>,
-) {
-    let coord = coord_q.single().unwrap();
-
-    for (tf, mut utf) in arrows_q.iter_mut() {
+fn system_sync_objects(mut arrows_q: Query<(Ref<Transform>, Ref<ObjectCoordinates>, &mut UserTransform)>) {
+    for (tf, coord, mut utf) in arrows_q.iter_mut() {
         if !tf.is_changed() && !coord.is_changed() {
             continue;
         }
@@ -185,7 +415,8 @@ fn system_sync_objects(
 
         utf.0 = Transform::default()
             .with_translation(pos)
-            .with_rotation(quat);
+            .with_rotation(quat)
+            .with_scale(convert_scale(&coord.internal2user, tf.scale));
     }
 }
 
@@ -197,6 +428,26 @@ pub enum AppliedTransform {
     RotationMat(Mat3),
     RotationEuler(Vec3),
     TransformMat(Mat4),
+    Scale(Vec3),
+    /// Points the forward direction of the object at the target, `up` is a hint for the up direction
+    LookAt { target: LookTarget, up: Vec3 },
+}
+
+/// Replacement for an up hint that is parallel to the look direction
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LookUpFallback {
+    /// the current up direction of the object
+    CurrentUp,
+    /// the current up is parallel too, any perpendicular direction is used
+    Arbitrary,
+}
+
+/// Vectors and points are in user coordinates, points ignore the rotated position mode
+#[derive(Clone, Copy, PartialEq)]
+pub enum LookTarget {
+    Direction(Vec3),
+    Point(Vec3),
+    Arrow(Entity),
 }
 
 #[derive(Event)]
@@ -247,21 +498,49 @@ impl ApplyTransformCommand {
             transform: AppliedTransform::TransformMat(mat),
         }
     }
+
+    pub fn scale(target: Entity, scale: Vec3) -> Self {
+        ApplyTransformCommand {
+            target,
+            transform: AppliedTransform::Scale(scale),
+        }
+    }
+
+    pub fn look_at(target: Entity, look: LookTarget, up: Vec3) -> Self {
+        ApplyTransformCommand {
+            target,
+            transform: AppliedTransform::LookAt { target: look, up },
+        }
+    }
 }
 
 fn system_process_transform_commands(
     mut events: EventReader<ApplyTransformCommand>,
-    coord_q: Query<&CoordinateSystem>,
-    mut arrows_q: Query<(&mut Transform, &UserTransform)>,
+    mut arrows_q: Query<(
+        &mut Transform,
+        &UserTransform,
+        &ObjectCoordinates,
+        &ComputedRepresentation,
+        Option<&mut crate::arrow::ArrowIO>,
+    )>,
 ) {
-    let coord = coord_q.single().unwrap();
-
     for event in events.read() {
-        let (mut tf, utf) = if let Ok(x) = arrows_q.get_mut(event.target) {
+        // has to be read before the target is borrowed mutably
+        let other_arrow_pos = match event.transform {
+            AppliedTransform::LookAt { target: LookTarget::Arrow(other), .. } => {
+                arrows_q.get(other).ok().map(|(tf, ..)| tf.translation)
+            }
+            _ => None,
+        };
+
+        let (mut tf, utf, coord, repr, arrow_io) = if let Ok(x) = arrows_q.get_mut(event.target) {
             x
         } else {
             continue;
         };
+        if repr.locked && !matches!(event.transform, AppliedTransform::Recompute) {
+            continue;
+        }
 
         match event.transform {
             AppliedTransform::Recompute => {
@@ -288,12 +567,130 @@ fn system_process_transform_commands(
             }
 
             AppliedTransform::TransformMat(mat) => {
-                let mat = Transform::from_matrix(mat);
+                // shear and the projective row can't be represented, they are dropped
+                let Some(mat) = TransformDecomposition::new(mat) else {
+                    continue;
+                };
 
-                tf.scale = mat.scale;
+                tf.scale = convert_scale(&coord.user2internal, mat.scale);
                 tf.rotation = convert_rotation(&coord.user2internal, mat.rotation);
                 tf.translation = convert_position_u2i(&coord.user2internal, coord.positions_scale, coord.position_mode, tf.rotation, mat.translation);
             }
+
+            AppliedTransform::Scale(scale) => {
+                tf.scale = convert_scale(&coord.user2internal, scale);
+            }
+
+            AppliedTransform::LookAt { target, up } => {
+                // internal forward and up of objects are -Z and Y, same as for looking_to
+                let direction = match target {
+                    LookTarget::Direction(direction) => coord.user2internal * direction,
+                    LookTarget::Point(point) => {
+                        convert_position_u2i(&coord.user2internal, coord.positions_scale, PositionMode::Flat, tf.rotation, point) - tf.translation
+                    }
+                    LookTarget::Arrow(_) => match other_arrow_pos {
+                        Some(pos) => pos - tf.translation,
+                        None => continue,
+                    },
+                };
+                if direction.length_squared() < 1e-12 {
+                    continue;
+                }
+
+                // up has to be replaced if it is parallel to the direction,
+                // the current up of the object is kept as close as possible in that case
+                let up = coord.user2internal * up;
+                let degenerate = direction.cross(up).length_squared() < 1e-8 * direction.length_squared() * up.length_squared();
+                let (up, fallback) = if !degenerate {
+                    (up, None)
+                } else if direction.cross(tf.rotation * Vec3::Y).length_squared() > 1e-8 * direction.length_squared() {
+                    (tf.rotation * Vec3::Y, Some(LookUpFallback::CurrentUp))
+                } else {
+                    (direction.any_orthonormal_vector(), Some(LookUpFallback::Arbitrary))
+                };
+
+                if let Some(mut arrow_io) = arrow_io {
+                    arrow_io.look_fallback = fallback;
+                }
+
+                tf.rotation = Transform::default().looking_to(direction, up).rotation;
+                tf.translation = convert_position_u2i(&coord.user2internal, coord.positions_scale, coord.position_mode, tf.rotation, utf.translation);
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trip(mat: Mat4) -> TransformDecomposition {
+        let parts = TransformDecomposition::new(mat).unwrap();
+        let result = parts.to_matrix();
+        assert!(result.abs_diff_eq(mat, 1e-5), "{mat} was recomposed as {result}");
+        parts
+    }
+
+    #[test]
+    fn decompose_rotation() {
+        let rotation = Quat::from_euler(EulerRot::XYZ, 0.5, -1.0, 2.5);
+        let parts = assert_round_trip(Mat4::from_rotation_translation(rotation, Vec3::new(1.0, 2.0, 3.0)));
+        assert!(parts.scale.abs_diff_eq(Vec3::ONE, 1e-5));
+        assert!(!parts.has_shear() && !parts.is_projective());
+        assert!(parts.rotation.dot(rotation).abs() > 1.0 - 1e-5);
+    }
+
+    #[test]
+    fn decompose_non_uniform_scale() {
+        let scale = Vec3::new(2.0, 0.5, 3.0);
+        let parts = assert_round_trip(Mat4::from_scale_rotation_translation(scale, Quat::from_rotation_y(0.8), Vec3::X));
+        assert!(parts.scale.abs_diff_eq(scale, 1e-5));
+        assert!(!parts.has_shear());
+    }
+
+    #[test]
+    fn decompose_shear() {
+        let shear = Mat3::from_cols(Vec3::X, Vec3::new(0.5, 1.0, 0.0), Vec3::new(-0.3, 0.2, 1.0));
+        let linear = Mat3::from_rotation_z(1.0) * shear * Mat3::from_diagonal(Vec3::new(1.0, 2.0, 0.5));
+        let parts = assert_round_trip(Mat4::from_mat3(linear));
+        assert!(parts.has_shear());
+        assert!(parts.shear.abs_diff_eq(Vec3::new(0.5, -0.3, 0.2), 1e-5), "{}", parts.shear);
+    }
+
+    #[test]
+    fn decompose_reflection() {
+        let linear = Mat3::from_rotation_x(0.6) * Mat3::from_cols(Vec3::X, Vec3::new(0.4, 1.0, 0.0), Vec3::Z)
+            * Mat3::from_diagonal(Vec3::new(1.0, 1.5, -2.0));
+        assert!(linear.determinant() < 0.0);
+        let parts = assert_round_trip(Mat4::from_mat3(linear));
+        assert!(parts.scale.x < 0.0);
+        assert!(parts.rotation.is_normalized());
+    }
+
+    #[test]
+    fn decompose_projective() {
+        let mut mat = Mat4::from_scale(Vec3::splat(2.0));
+        mat.x_axis.w = 0.25;
+        let parts = assert_round_trip(mat);
+        assert!(parts.is_projective());
+    }
+
+    #[test]
+    fn decompose_degenerate() {
+        assert!(TransformDecomposition::new(Mat4::from_scale(Vec3::new(1.0, 0.0, 1.0))).is_none());
+    }
+
+    #[test]
+    fn named_coordinates_kept_after_rename() {
+        let mut named = ui::NamedCoordinates::new("Z up".into());
+        named.hand = Hand::Left;
+        let coordinates = Coordinates::Named(named.id);
+        let mut extra = ui::ExtraCoordinateSystems(vec![named]);
+        extra.0[0].name = "Renamed".into();
+
+        let main = CoordinateSystem::default();
+        let systems = CoordinateSystems { main: &main, extra: &extra };
+        assert!(systems.resolve(&coordinates) == extra.0[0].coordinate_system());
+        assert!(systems.resolve(&coordinates) != main);
+    }
+}
@@ -0,0 +1,513 @@
+// History works with snapshots of the whole scene instead of individual commands,
+// so that every kind of edit can be undone without each of them having to be recorded explicitly.
+// Transforms are stored in user coordinates, which keeps them correct when undo also changes the coordinate system
+
+use std::collections::HashMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_egui::EguiContexts;
+
+use crate::{
+    geometry::{self, GeometrySet, ObjectCoordinates, UserTransform},
+    group::{InGroup, InGroupDisplaySettings},
+    objects::{Arrow, Group, IsObject, ObjectId, probe::Probe},
+    repr::ReprSettings,
+    scene::{ObjectData, ObjectKind},
+    ui::{ConfigIO, ExtraCoordinateSystems},
+};
+
+const HISTORY_LIMIT: usize = 100;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>()
+            .add_event::<HistoryCommand>()
+            .add_systems(
+                Update,
+                (
+                    system_history_shortcuts,
+                    system_apply_pending_transforms,
+                    system_process_history_commands,
+                )
+                    .chain()
+                    .before(GeometrySet),
+            )
+            .add_systems(Update, system_record_history.after(GeometrySet));
+    }
+}
+
+#[derive(Event, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryCommand {
+    Undo,
+    Redo,
+}
+
+#[derive(Clone, Deref)]
+struct ObjectSnapshot {
+    id: ObjectId,
+    /// `None` for objects at the top level
+    parent: Option<ObjectId>,
+    popped_out: Option<bool>,
+    #[deref]
+    data: ObjectData,
+}
+
+#[derive(Clone)]
+struct SceneSnapshot {
+    config: ConfigIO,
+    default_repr: ReprSettings,
+    extra_coordinates: ExtraCoordinateSystems,
+    /// sorted by id
+    objects: Vec<ObjectSnapshot>,
+}
+
+impl SceneSnapshot {
+    /// Transforms go through user -> internal -> user conversion when restored,
+    /// so they are compared approximately
+    fn same_as(&self, other: &SceneSnapshot) -> bool {
+        let same_transform = |a: &Option<Transform>, b: &Option<Transform>| match (a, b) {
+            (Some(a), Some(b)) => {
+                a.translation.abs_diff_eq(b.translation, 1e-4)
+                    && a.rotation.abs_diff_eq(b.rotation, 1e-4)
+                    && a.scale.abs_diff_eq(b.scale, 1e-4)
+            }
+            (a, b) => a.is_none() && b.is_none(),
+        };
+
+        self.config == other.config
+            && self.default_repr == other.default_repr
+            && self.extra_coordinates == other.extra_coordinates
+            && self.objects.len() == other.objects.len()
+            && self.objects.iter().zip(&other.objects).all(|(a, b)| {
+                a.id == b.id
+                    && a.kind == b.kind
+                    && a.parent == b.parent
+                    && a.name == b.name
+                    && a.repr == b.repr
+                    && a.popped_out == b.popped_out
+                    && a.probe == b.probe
+                    && same_transform(&a.transform, &b.transform)
+            })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct History {
+    undo: Vec<SceneSnapshot>,
+    redo: Vec<SceneSnapshot>,
+    current: Option<SceneSnapshot>,
+    pending: Option<PendingTransforms>,
+}
+
+impl History {
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+/// Transforms of a restored snapshot can only be converted to internal coordinates
+/// after the restored coordinate system has been applied, which happens a frame later
+struct PendingTransforms {
+    transforms: Vec<(ObjectId, Transform)>,
+    wait: bool,
+}
+
+fn system_history_shortcuts(
+    mut ctx: EguiContexts,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut events: EventWriter<HistoryCommand>,
+) {
+    // text fields have their own undo
+    if let Ok(ctx) = ctx.ctx_mut()
+        && ctx.wants_keyboard_input()
+    {
+        return;
+    }
+
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if ctrl && keys.just_pressed(KeyCode::KeyZ) {
+        events.write(if shift { HistoryCommand::Redo } else { HistoryCommand::Undo });
+    } else if ctrl && keys.just_pressed(KeyCode::KeyY) {
+        events.write(HistoryCommand::Redo);
+    }
+}
+
+fn system_apply_pending_transforms(
+    mut history: ResMut<History>,
+    mut arrows_q: Query<(&ObjectId, &ObjectCoordinates, &mut Transform), With<Arrow>>,
+) {
+    let Some(pending) = &mut history.pending else {
+        return;
+    };
+    if pending.wait {
+        pending.wait = false;
+        return;
+    }
+
+    let transforms: HashMap<_, _> = history.pending.take().unwrap().transforms.into_iter().collect();
+
+    // objects are restored in their groups a frame earlier, so their coordinate systems are already resolved
+    for (id, coord, mut tf) in arrows_q.iter_mut() {
+        if let Some(utf) = transforms.get(id) {
+            tf.rotation = geometry::convert_rotation(&coord.user2internal, utf.rotation);
+            tf.translation = geometry::convert_position_u2i(
+                &coord.user2internal,
+                coord.positions_scale,
+                coord.position_mode,
+                tf.rotation,
+                utf.translation,
+            );
+            tf.scale = geometry::convert_scale(&coord.user2internal, utf.scale);
+        }
+    }
+}
+
+type RestoredConfigQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut ConfigIO,
+        &'static mut ReprSettings,
+        &'static mut ExtraCoordinateSystems,
+    ),
+>;
+
+type RestoredObjectsQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static ObjectId,
+        &'static mut Name,
+        &'static mut ReprSettings,
+        Option<&'static InGroup>,
+        Option<&'static mut InGroupDisplaySettings>,
+        Option<&'static Probe>,
+    ),
+    (IsObject, Without<ConfigIO>),
+>;
+
+fn system_process_history_commands(
+    mut cmd: Commands,
+    mut events: EventReader<HistoryCommand>,
+    mut history: ResMut<History>,
+    mut config_q: RestoredConfigQuery,
+    mut objects_q: RestoredObjectsQuery,
+) {
+    let history = &mut *history;
+
+    let mut restored = None;
+    for event in events.read() {
+        let (from, to) = match event {
+            HistoryCommand::Undo => (&mut history.undo, &mut history.redo),
+            HistoryCommand::Redo => (&mut history.redo, &mut history.undo),
+        };
+        if let Some(snapshot) = from.pop() {
+            to.extend(history.current.replace(snapshot.clone()));
+            restored = Some(snapshot);
+        }
+    }
+    let Some(snapshot) = restored else {
+        return;
+    };
+
+    let (config_ent, mut config, mut default_repr, mut extra_coordinates) = config_q.single_mut().unwrap();
+    config.set_if_neq(snapshot.config.clone());
+    default_repr.set_if_neq(snapshot.default_repr.clone());
+    // restored together with the representations of groups that refer to them by name
+    extra_coordinates.set_if_neq(snapshot.extra_coordinates.clone());
+
+    let mut entities: HashMap<ObjectId, Entity> = objects_q
+        .iter()
+        .map(|(ent, id, ..)| (*id, ent))
+        .collect();
+
+    // spawn deleted objects first, so that everything can be put in its group afterwards
+    for object in &snapshot.objects {
+        if entities.contains_key(&object.id) {
+            continue;
+        }
+
+        let mut ent = cmd.spawn((
+            object.id,
+            Name::new(object.name.clone()),
+            object.repr.clone(),
+        ));
+        match object.kind {
+            ObjectKind::Arrow => ent.insert(Arrow),
+            ObjectKind::Group => ent.insert(Group),
+        };
+        if let Some(popped_out) = object.popped_out {
+            ent.insert(InGroupDisplaySettings { popped_out });
+        }
+        if let Some(probe) = object.probe {
+            ent.insert(probe);
+        }
+        entities.insert(object.id, ent.id());
+    }
+
+    for object in &snapshot.objects {
+        let ent = entities[&object.id];
+        let group = InGroup(object.parent.map_or(config_ent, |parent| entities[&parent]));
+
+        let Ok((_, _, mut name, mut repr, in_group, display, probe)) = objects_q.get_mut(ent) else {
+            // newly spawned
+            cmd.entity(ent).insert(group);
+            continue;
+        };
+
+        if in_group != Some(&group) {
+            cmd.entity(ent).insert(group);
+        }
+        if name.as_str() != object.name {
+            name.set(object.name.clone());
+        }
+        repr.set_if_neq(object.repr.clone());
+        if let (Some(mut display), Some(popped_out)) = (display, object.popped_out) {
+            display.popped_out = popped_out;
+        }
+        match (probe, object.probe) {
+            (Some(_), None) => {
+                cmd.entity(ent).remove::<Probe>();
+            }
+            (probe, Some(restored)) if probe != Some(&restored) => {
+                cmd.entity(ent).insert(restored);
+            }
+            _ => {}
+        }
+    }
+
+    // despawned after regrouping, so that objects which are kept don't get despawned with their old group
+    for (ent, id, ..) in objects_q.iter() {
+        if snapshot.objects.binary_search_by_key(id, |object| object.id).is_err() {
+            cmd.entity(ent).try_despawn();
+        }
+    }
+
+    history.pending = Some(PendingTransforms {
+        transforms: snapshot
+            .objects
+            .iter()
+            .filter_map(|object| Some((object.id, object.transform?)))
+            .collect(),
+        wait: true,
+    });
+}
+
+/// The snapshot is only built and compared after something changed
+#[derive(Default)]
+struct UnrecordedChanges(bool);
+
+type RecordedConfigQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        Ref<'static, ConfigIO>,
+        Ref<'static, ReprSettings>,
+        Ref<'static, ExtraCoordinateSystems>,
+    ),
+>;
+
+type RecordedObjectsQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static ObjectId,
+        Has<Arrow>,
+        &'static Name,
+        &'static ReprSettings,
+        Option<&'static InGroup>,
+        Option<&'static UserTransform>,
+        Option<&'static InGroupDisplaySettings>,
+        Option<&'static Probe>,
+    ),
+    (IsObject, Without<ConfigIO>),
+>;
+
+type ChangedObjectsFilter = (
+    IsObject,
+    Or<(
+        Changed<ObjectId>,
+        Changed<Name>,
+        Changed<ReprSettings>,
+        Changed<InGroup>,
+        Changed<UserTransform>,
+        Changed<InGroupDisplaySettings>,
+        Changed<Probe>,
+    )>,
+);
+
+/// Changes of the recorded components of objects, removed objects aren't included
+#[derive(SystemParam)]
+struct ObjectChanges<'w, 's> {
+    changed_q: Query<'w, 's, (), ChangedObjectsFilter>,
+    removed_probes: RemovedComponents<'w, 's, Probe>,
+}
+
+impl ObjectChanges<'_, '_> {
+    fn any(&mut self) -> bool {
+        // removed components are always read, so that they don't pile up
+        let removed = self.removed_probes.read().count() > 0;
+        removed || !self.changed_q.is_empty()
+    }
+}
+
+/// Edits that are still in progress (dragging a value, typing a name) are recorded once they are finished
+#[derive(SystemParam)]
+struct EditInProgress<'w, 's> {
+    ctx: EguiContexts<'w, 's>,
+    input_mouse: Res<'w, ButtonInput<MouseButton>>,
+}
+
+impl EditInProgress<'_, '_> {
+    fn get(&mut self) -> bool {
+        self.input_mouse.get_pressed().next().is_some()
+            || self.ctx.ctx_mut().is_ok_and(|ctx| ctx.memory(|mem| mem.focused().is_some()))
+    }
+}
+
+fn system_record_history(
+    mut history: ResMut<History>,
+    mut unrecorded: Local<UnrecordedChanges>,
+    mut edit_in_progress: EditInProgress,
+    config_q: RecordedConfigQuery,
+    objects_q: RecordedObjectsQuery,
+    mut changes: ObjectChanges,
+    ids_q: Query<&ObjectId>,
+) {
+    let (config_ent, config, default_repr, extra_coordinates) = config_q.single().unwrap();
+
+    // removed objects are noticed by the count
+    unrecorded.0 |= changes.any()
+        || config.is_changed()
+        || default_repr.is_changed()
+        || extra_coordinates.is_changed()
+        || history.current.as_ref().is_none_or(|current| current.objects.len() != objects_q.iter().len());
+
+    if history.pending.is_some() || !unrecorded.0 || edit_in_progress.get() {
+        return;
+    }
+    unrecorded.0 = false;
+
+    let mut objects: Vec<_> = objects_q
+        .iter()
+        .map(|(id, is_arrow, name, repr, in_group, utf, display, probe)| ObjectSnapshot {
+            id: *id,
+            parent: in_group
+                .filter(|in_group| in_group.0 != config_ent)
+                .and_then(|in_group| ids_q.get(in_group.0).ok().copied()),
+            popped_out: display.map(|display| display.popped_out),
+            data: ObjectData::new(is_arrow, name, repr, utf, probe),
+        })
+        .collect();
+    objects.sort_by_key(|object| object.id);
+
+    let snapshot = SceneSnapshot {
+        config: config.clone(),
+        default_repr: default_repr.clone(),
+        extra_coordinates: extra_coordinates.clone(),
+        objects,
+    };
+
+    match &history.current {
+        Some(current) if current.same_as(&snapshot) => {}
+        Some(_) => {
+            let previous = history.current.replace(snapshot).unwrap();
+            history.undo.push(previous);
+            if history.undo.len() > HISTORY_LIMIT {
+                history.undo.remove(0);
+            }
+            history.redo.clear();
+        }
+        None => history.current = Some(snapshot),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::{CoordinateSystem, CoordinateSystems},
+        repr::Coordinates,
+        ui::NamedCoordinates,
+    };
+
+    fn test_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<bevy_egui::EguiUserTextures>()
+            .add_plugins(HistoryPlugin);
+        app
+    }
+
+    #[test]
+    fn undo_named_coordinates_delete() {
+        let mut app = test_app();
+        let named = NamedCoordinates::new("Z up".into());
+        let coordinates = Coordinates::Named(named.id);
+        let config = app.world_mut().spawn((ConfigIO::default(), ExtraCoordinateSystems(vec![named]))).id();
+        let group = app
+            .world_mut()
+            .spawn((
+                Group,
+                ObjectId(1),
+                Name::new("group"),
+                ReprSettings { coordinates: Some(coordinates.clone()), ..default() },
+                InGroup(config),
+            ))
+            .id();
+        app.update();
+
+        app.world_mut().get_mut::<ExtraCoordinateSystems>(config).unwrap().0.clear();
+        app.update();
+        assert!(app.world().resource::<History>().can_undo());
+
+        app.world_mut().send_event(HistoryCommand::Undo);
+        app.update();
+
+        let world = app.world();
+        let extra = world.get::<ExtraCoordinateSystems>(config).unwrap();
+        assert_eq!(extra.0.len(), 1);
+        assert_eq!(world.get::<ReprSettings>(group).unwrap().coordinates, Some(coordinates.clone()));
+
+        let main = CoordinateSystem::default();
+        let systems = CoordinateSystems { main: &main, extra };
+        assert!(systems.resolve(&coordinates) == extra.0[0].coordinate_system());
+        assert!(systems.resolve(&coordinates) != main);
+    }
+
+    #[test]
+    fn undo_delete_restores_probe() {
+        let mut app = test_app();
+        let config = app.world_mut().spawn(ConfigIO::default()).id();
+        let probe = Probe { vector: Vec3::new(1.0, 2.0, 3.0), translate: true };
+        let arrow = app
+            .world_mut()
+            .spawn((Arrow, ObjectId(1), Name::new("arrow"), InGroup(config), probe))
+            .id();
+        app.update();
+
+        app.world_mut().despawn(arrow);
+        app.update();
+
+        app.world_mut().send_event(HistoryCommand::Undo);
+        app.update();
+
+        let world = app.world_mut();
+        let mut restored_q = world.query::<(&ObjectId, &Probe)>();
+        let (id, restored) = restored_q.single(world).unwrap();
+        assert_eq!(*id, ObjectId(1));
+        assert!(*restored == probe);
+    }
+}
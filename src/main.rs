@@ -1,16 +1,23 @@
 use bevy::color::palettes::css as pallette;
-use bevy::prelude::*;
+use bevy::{prelude::*, render::view::RenderLayers};
 use bevy_rich_text3d as text3d;
 
+mod animation;
 mod camera;
+mod export;
 mod geometry;
+mod history;
 mod mesh;
+mod model;
 mod repr;
+mod rotation;
+mod scene;
 mod conversion;
+mod selection;
 mod ui;
 
 pub mod objects;
-pub use objects::{arrow, group};
+pub use objects::{arrow, group, probe};
 
 fn main() {
     let mut app = App::new();
@@ -34,11 +41,20 @@ fn main() {
         })
         .add_plugins(ui::UiPlugins)
         .add_plugins(geometry::GeometryPlugin)
+        .add_plugins(selection::SelectionPlugin)
+        .add_plugins(model::ModelPlugin)
+        .add_plugins(export::ExportPlugin)
+        .add_plugins(history::HistoryPlugin)
+        .add_plugins(scene::ScenePlugin)
+        .add_plugins(animation::AnimationPlugin)
         .add_systems(Startup, setup)
         .add_systems(Update, camera::pan_orbit_camera)
-        .add_systems(Update, (arrow::system_init_arrow_names, arrow::system_sync_arrow_io, arrow::system_sync_display_arrow))
+        .add_systems(Update, (arrow::system_init_arrow_names, arrow::system_sync_arrow_io, (arrow::system_sync_display_arrow, arrow::system_sync_xray_layers).chain(), arrow::system_sync_arrow_visibility, arrow::system_draw_twist_arcs, arrow::system_draw_unnormalized_axes))
+        .add_systems(Update, probe::system_draw_probes)
         .add_systems(Update, group::system_init_group_names)
-        .add_systems(Update, repr::system_propagate_repr_settings)
+        .add_systems(Update, objects::system_init_object_ids)
+        // coordinate systems of objects are inherited with the representation
+        .add_systems(Update, repr::system_propagate_repr_settings.before(geometry::GeometrySet))
         ;
 
     app.run();
@@ -64,7 +80,21 @@ fn setup(
         },
         Transform::from_translation(Vec3::splat(1.0).normalize() * radius)
             .looking_at(Vec3::ZERO, Vec3::Y),
-    ));
+    ))
+    .with_children(|cmd| {
+        // draws x-ray objects over the rest of the scene, ui is drawn by this camera so that it stays on top
+        cmd.spawn((
+            Camera3d::default(),
+            Camera {
+                order: 1,
+                clear_color: ClearColorConfig::None,
+                ..default()
+            },
+            bevy::core_pipeline::tonemapping::Tonemapping::None,
+            RenderLayers::layer(repr::XRAY_LAYER),
+            bevy_egui::PrimaryEguiContext,
+        ));
+    });
 
     let mesh = meshes.add(mesh::create_plane_mesh());
     let material = materials.add(StandardMaterial {
@@ -83,11 +113,12 @@ fn setup(
 
     let axis_mesh = meshes.add(Cylinder::new(0.012, 1.0).mesh().resolution(10).segments(1));
 
-    for (axis, color, up) in [
-        (geometry::Axis::X, Color::from(pallette::RED), Vec3::Y),
-        (geometry::Axis::Y, Color::from(pallette::GREEN), Vec3::Z),
-        (geometry::Axis::Z, Color::from(pallette::BLUE), Vec3::Y),
+    for (axis, up) in [
+        (geometry::Axis::X, Vec3::Y),
+        (geometry::Axis::Y, Vec3::Z),
+        (geometry::Axis::Z, Vec3::Y),
     ] {
+        let color = axis.color();
         let material = materials.add(StandardMaterial {
             base_color: color,
             depth_bias: -0.5,
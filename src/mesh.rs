@@ -1,4 +1,12 @@
-use bevy::{asset::RenderAssetUsages, prelude::*, render::mesh::CylinderAnchor};
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{CylinderAnchor, VertexAttributeValues},
+};
+use bevy_rich_text3d as text3d;
+
+use crate::model::{Model, ModelLibrary};
+use crate::repr::Shape;
 
 pub fn create_plane_mesh() -> Mesh {
     let size: i16 = 5;
@@ -35,15 +43,82 @@ pub fn create_plane_mesh() -> Mesh {
     mesh
 }
 
+/// Marks the parent of the selection outline, which is not a part of the displayed shape
+#[derive(Component)]
+pub struct Outline;
+
+/// Materials for the parts of a shape that don't use the object's color
+pub struct ShapeDetails {
+    /// port side (-X)
+    pub left: Handle<StandardMaterial>,
+    /// starboard side (+X)
+    pub right: Handle<StandardMaterial>,
+    pub text: Handle<StandardMaterial>,
+}
+
+#[derive(Clone, Copy)]
+pub struct ShapeSize {
+    pub length: f32,
+    /// thickness of arrows and triad axes
+    pub radius_scale: f32,
+}
+
+/// Spawns the selected shape. Shapes other than the arrow are sized by `length`,
+/// point forward along -Z and up along +Y.
+/// `details` can be omitted to spawn only the main body (e.g. for outlines)
+pub fn spawn_shape(
+    meshes: &mut Assets<Mesh>,
+    cmd: &mut ChildSpawnerCommands,
+    models: &ModelLibrary,
+    shape: Shape,
+    size: ShapeSize,
+    material: Handle<StandardMaterial>,
+    details: Option<&ShapeDetails>,
+) {
+    let length = size.length;
+    match shape {
+        Shape::Arrow => spawn_arrow(meshes, cmd, size, material),
+        Shape::Airplane => spawn_airplane(meshes, cmd, length, material, details),
+        Shape::Box => spawn_box(meshes, cmd, length, material, details),
+        Shape::Frustum => spawn_frustum(meshes, cmd, length, material),
+        Shape::Die => spawn_die(meshes, cmd, length, material, details),
+        Shape::Model(id) => match models.get(id) {
+            Some(model) => spawn_model(cmd, model, length, material),
+            None => spawn_arrow(meshes, cmd, size, material),
+        },
+    }
+}
+
 pub fn spawn_arrow(
     meshes: &mut Assets<Mesh>,
     cmd: &mut ChildSpawnerCommands,
-    length: f32,
-    radius_scale: f32,
+    ShapeSize { length, radius_scale }: ShapeSize,
     material: Handle<StandardMaterial>,
 ) {
     let radius = 0.011 * radius_scale;
+    spawn_arrow_parts(meshes, cmd, length, radius, 0.2 * radius_scale, material);
+}
 
+/// Spawns a slightly thicker arrow around the one created by `spawn_arrow`.
+/// `material` is expected to cull front faces, so that only the outline remains visible
+pub fn spawn_arrow_outline(
+    meshes: &mut Assets<Mesh>,
+    cmd: &mut ChildSpawnerCommands,
+    ShapeSize { length, radius_scale }: ShapeSize,
+    material: Handle<StandardMaterial>,
+) {
+    let radius = 0.011 * radius_scale + 0.006;
+    spawn_arrow_parts(meshes, cmd, length, radius, 0.2 * radius_scale, material);
+}
+
+fn spawn_arrow_parts(
+    meshes: &mut Assets<Mesh>,
+    cmd: &mut ChildSpawnerCommands,
+    length: f32,
+    radius: f32,
+    hook_length: f32,
+    material: Handle<StandardMaterial>,
+) {
     let base = Cylinder::new(radius, length)
         .mesh()
         .anchor(CylinderAnchor::Bottom)
@@ -55,7 +130,7 @@ pub fn spawn_arrow(
         MeshMaterial3d(material.clone()),
     ));
 
-    let hook = Cylinder::new(radius, 0.2 * radius_scale)
+    let hook = Cylinder::new(radius, hook_length)
         .mesh()
         .anchor(CylinderAnchor::Bottom)
         .resolution(10)
@@ -75,3 +150,239 @@ pub fn spawn_arrow(
         MeshMaterial3d(material),
     ));
 }
+
+/// Spawns a single axis of a local frame triad, pointing along `direction` in the parent's space
+pub fn spawn_triad_axis(
+    meshes: &mut Assets<Mesh>,
+    cmd: &mut ChildSpawnerCommands,
+    direction: Vec3,
+    ShapeSize { length, radius_scale }: ShapeSize,
+    material: Handle<StandardMaterial>,
+    label: Option<(&'static str, Handle<StandardMaterial>)>,
+) {
+    let radius = 0.008 * radius_scale;
+
+    let axis = Cylinder::new(radius, length)
+        .mesh()
+        .anchor(CylinderAnchor::Bottom)
+        .resolution(10)
+        .segments(1);
+
+    cmd.spawn((
+        Transform::from_rotation(Quat::from_rotation_arc(Vec3::Y, direction.normalize())),
+        Visibility::default(),
+    ))
+    .with_children(|cmd| {
+        cmd.spawn((Mesh3d(meshes.add(axis)), MeshMaterial3d(material)));
+
+        if let Some((label, text_material)) = label {
+            cmd.spawn((
+                Transform::from_xyz(0.0, length + 0.02, 0.0),
+                text3d::Text3d::new(label),
+                text3d::Text3dStyling {
+                    size: 64.0,
+                    world_scale: Some(Vec2::splat(0.07)),
+                    anchor: text3d::TextAnchor::BOTTOM_CENTER,
+                    ..default()
+                },
+                Mesh3d::default(),
+                MeshMaterial3d(text_material),
+            ));
+        }
+    });
+}
+
+/// Adds vertex colors that imitate a light fixed relative to the mesh,
+/// since everything in the scene is unlit
+pub fn shaded(mesh: impl Into<Mesh>) -> Mesh {
+    let mut mesh = mesh.into();
+    let light = Vec3::new(0.3, 1.0, 0.5).normalize();
+
+    let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL) else {
+        return mesh;
+    };
+    let colors: Vec<[f32; 4]> = normals
+        .iter()
+        .map(|normal| {
+            let brightness = 0.65 + 0.35 * Vec3::from(*normal).dot(light);
+            [brightness, brightness, brightness, 1.0]
+        })
+        .collect();
+
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh
+}
+
+fn spawn_airplane(
+    meshes: &mut Assets<Mesh>,
+    cmd: &mut ChildSpawnerCommands,
+    length: f32,
+    material: Handle<StandardMaterial>,
+    details: Option<&ShapeDetails>,
+) {
+    let l = length;
+
+    let fuselage = Capsule3d::new(0.06 * l, 0.8 * l).mesh().latitudes(8).longitudes(12);
+    cmd.spawn((
+        Transform::from_rotation(Quat::from_rotation_x(std::f32::consts::TAU / 4.0)),
+        Mesh3d(meshes.add(shaded(fuselage))),
+        MeshMaterial3d(material.clone()),
+    ));
+
+    let parts = [
+        // wings
+        (Cuboid::new(0.9 * l, 0.02 * l, 0.22 * l), Vec3::new(0.0, 0.0, -0.05 * l)),
+        // horizontal stabilizer
+        (Cuboid::new(0.35 * l, 0.02 * l, 0.12 * l), Vec3::new(0.0, 0.0, 0.38 * l)),
+        // vertical stabilizer
+        (Cuboid::new(0.02 * l, 0.2 * l, 0.14 * l), Vec3::new(0.0, 0.13 * l, 0.38 * l)),
+    ];
+    for (part, pos) in parts {
+        cmd.spawn((
+            Transform::from_translation(pos),
+            Mesh3d(meshes.add(shaded(part))),
+            MeshMaterial3d(material.clone()),
+        ));
+    }
+
+    if let Some(details) = details {
+        let light = meshes.add(Sphere::new(0.03 * l).mesh().ico(2).unwrap());
+        for (x, material) in [(-0.45 * l, &details.left), (0.45 * l, &details.right)] {
+            cmd.spawn((
+                Transform::from_xyz(x, 0.0, -0.05 * l),
+                Mesh3d(light.clone()),
+                MeshMaterial3d(material.clone()),
+            ));
+        }
+    }
+}
+
+fn spawn_box(
+    meshes: &mut Assets<Mesh>,
+    cmd: &mut ChildSpawnerCommands,
+    length: f32,
+    material: Handle<StandardMaterial>,
+    details: Option<&ShapeDetails>,
+) {
+    let l = length;
+
+    cmd.spawn((
+        Mesh3d(meshes.add(shaded(Cuboid::new(0.5 * l, 0.25 * l, l)))),
+        MeshMaterial3d(material.clone()),
+    ));
+
+    // marks the top front corner, so that up and forward can't be confused with down and backward
+    cmd.spawn((
+        Transform::from_xyz(0.0, 0.175 * l, -0.35 * l),
+        Mesh3d(meshes.add(shaded(Cuboid::new(0.15 * l, 0.1 * l, 0.15 * l)))),
+        MeshMaterial3d(material),
+    ));
+
+    if let Some(details) = details {
+        let plate = meshes.add(Cuboid::new(0.01 * l, 0.15 * l, 0.6 * l));
+        for (x, material) in [(-0.255 * l, &details.left), (0.255 * l, &details.right)] {
+            cmd.spawn((
+                Transform::from_xyz(x, 0.0, 0.0),
+                Mesh3d(plate.clone()),
+                MeshMaterial3d(material.clone()),
+            ));
+        }
+    }
+}
+
+fn spawn_frustum(
+    meshes: &mut Assets<Mesh>,
+    cmd: &mut ChildSpawnerCommands,
+    length: f32,
+    material: Handle<StandardMaterial>,
+) {
+    let l = length;
+    let (w, h) = (0.4 * l, 0.3 * l);
+
+    let mut lines = Mesh::new(
+        bevy::render::render_resource::PrimitiveTopology::LineList,
+        RenderAssetUsages::default(),
+    );
+    lines.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![
+            Vec3::ZERO,
+            Vec3::new(-w, -h, -l),
+            Vec3::new(w, -h, -l),
+            Vec3::new(w, h, -l),
+            Vec3::new(-w, h, -l),
+            // triangle above the top edge marks the up direction
+            Vec3::new(-0.5 * w, 1.1 * h, -l),
+            Vec3::new(0.5 * w, 1.1 * h, -l),
+            Vec3::new(0.0, 1.6 * h, -l),
+        ],
+    );
+    lines.insert_indices(bevy::render::mesh::Indices::U16(vec![
+        0, 1, 0, 2, 0, 3, 0, 4, //
+        1, 2, 2, 3, 3, 4, 4, 1, //
+        5, 6, 6, 7, 7, 5,
+    ]));
+    cmd.spawn((Mesh3d(meshes.add(lines)), MeshMaterial3d(material.clone())));
+
+    // camera body behind the apex, also makes the shape pickable
+    cmd.spawn((
+        Transform::from_xyz(0.0, 0.0, 0.125 * l),
+        Mesh3d(meshes.add(shaded(Cuboid::new(0.2 * l, 0.15 * l, 0.25 * l)))),
+        MeshMaterial3d(material),
+    ));
+}
+
+fn spawn_die(
+    meshes: &mut Assets<Mesh>,
+    cmd: &mut ChildSpawnerCommands,
+    length: f32,
+    material: Handle<StandardMaterial>,
+    details: Option<&ShapeDetails>,
+) {
+    let half = 0.25 * length;
+
+    cmd.spawn((
+        Mesh3d(meshes.add(shaded(Cuboid::from_length(2.0 * half)))),
+        MeshMaterial3d(material),
+    ));
+
+    let Some(details) = details else {
+        return;
+    };
+
+    // opposite faces add up to 7, 1 is forward, 2 is up and 3 is to the right
+    for (label, normal, up) in [
+        ("1", Vec3::NEG_Z, Vec3::Y),
+        ("6", Vec3::Z, Vec3::Y),
+        ("2", Vec3::Y, Vec3::NEG_Z),
+        ("5", Vec3::NEG_Y, Vec3::Z),
+        ("3", Vec3::X, Vec3::Y),
+        ("4", Vec3::NEG_X, Vec3::Y),
+    ] {
+        cmd.spawn((
+            Transform::from_translation(normal * half * 1.01).looking_to(-normal, up),
+            text3d::Text3d::new(label),
+            text3d::Text3dStyling {
+                size: 64.0,
+                world_scale: Some(Vec2::splat(half * 1.2)),
+                anchor: text3d::TextAnchor::CENTER,
+                ..default()
+            },
+            Mesh3d::default(),
+            MeshMaterial3d(details.text.clone()),
+        ));
+    }
+}
+
+fn spawn_model(
+    cmd: &mut ChildSpawnerCommands,
+    model: &Model,
+    length: f32,
+    material: Handle<StandardMaterial>,
+) {
+    cmd.spawn((
+        model.correction(length),
+        Mesh3d(model.mesh.clone()),
+        MeshMaterial3d(material),
+    ));
+}
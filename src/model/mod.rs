@@ -0,0 +1,198 @@
+use std::path::Path;
+
+use bevy::{asset::RenderAssetUsages, prelude::*, render::render_resource::PrimitiveTopology};
+
+use crate::{
+    geometry::{self, Axis, Hand},
+    repr, selection,
+};
+
+mod parse;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+pub struct ModelPlugin;
+
+impl Plugin for ModelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ModelLibrary>()
+            .add_systems(Update, system_load_dropped_models);
+
+        #[cfg(target_arch = "wasm32")]
+        app.init_resource::<web::DroppedFiles>()
+            .add_systems(Startup, web::setup_drop_listener)
+            .add_systems(Update, system_load_dropped_web_files);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModelId(pub usize);
+
+/// Mesh loaded from a file, along with the conventions it was authored in
+pub struct Model {
+    pub name: String,
+    pub mesh: Handle<Mesh>,
+    pub center: Vec3,
+    /// largest dimension of the bounding box, used to fit the model to the object length
+    pub size: f32,
+
+    pub up: Axis,
+    pub up_sign: f32,
+    pub forward: Axis,
+    pub forward_sign: f32,
+    pub hand: Hand,
+}
+
+impl Model {
+    /// Transform from model space into the object's internal space
+    pub fn correction(&self, length: f32) -> Transform {
+        let basis = geometry::user2internal_basis(
+            self.up.to_vec() * self.up_sign,
+            self.forward.to_vec() * self.forward_sign,
+            self.hand,
+        );
+        let mat = Mat4::from_mat3(basis)
+            * Mat4::from_scale(Vec3::splat(length / self.size))
+            * Mat4::from_translation(-self.center);
+
+        Transform::from_matrix(mat)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ModelLibrary {
+    pub models: Vec<Model>,
+    /// error from the last failed load, displayed in the settings
+    pub error: Option<String>,
+}
+
+impl ModelLibrary {
+    pub fn get(&self, id: ModelId) -> Option<&Model> {
+        self.models.get(id.0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ModelId, &Model)> {
+        self.models.iter().enumerate().map(|(i, model)| (ModelId(i), model))
+    }
+
+    pub fn load_file(&mut self, meshes: &mut Assets<Mesh>, path: &Path) -> Option<ModelId> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        match std::fs::read(path) {
+            Ok(bytes) => self.load(meshes, &name, &bytes, path.parent()),
+            Err(err) => {
+                self.error = Some(format!("{}: {err}", path.display()));
+                None
+            }
+        }
+    }
+
+    /// Format is determined by the extension of `name`
+    pub fn load(
+        &mut self,
+        meshes: &mut Assets<Mesh>,
+        name: &str,
+        bytes: &[u8],
+        base_dir: Option<&Path>,
+    ) -> Option<ModelId> {
+        let extension = Path::new(name)
+            .extension()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        // defaults are the most common conventions for each format
+        let (parsed, up, forward) = match extension.to_str() {
+            Some("obj") => (
+                parse::parse_obj(&String::from_utf8_lossy(bytes)),
+                Axis::Y,
+                (Axis::Z, -1.0),
+            ),
+            Some("stl") => (parse::parse_stl(bytes), Axis::Z, (Axis::Y, 1.0)),
+            Some("gltf" | "glb") => (parse::parse_gltf(bytes, base_dir), Axis::Y, (Axis::Z, 1.0)),
+            _ => {
+                self.error = Some(format!("{name}: unsupported format, expected obj, stl, gltf or glb"));
+                return None;
+            }
+        };
+
+        let triangles = match parsed {
+            Ok(triangles) if !triangles.positions.is_empty() => triangles,
+            Ok(_) => {
+                self.error = Some(format!("{name}: file contains no triangles"));
+                return None;
+            }
+            Err(err) => {
+                self.error = Some(format!("{name}: {err}"));
+                return None;
+            }
+        };
+
+        let (min, max) = triangles.positions.iter().fold(
+            (Vec3::INFINITY, Vec3::NEG_INFINITY),
+            |(min, max), pos| (min.min(*pos), max.max(*pos)),
+        );
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, triangles.positions);
+        match triangles.normals {
+            Some(normals) => mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals),
+            None => mesh.compute_flat_normals(),
+        }
+
+        self.error = None;
+        self.models.push(Model {
+            name: name.to_string(),
+            mesh: meshes.add(crate::mesh::shaded(mesh)),
+            center: (min + max) / 2.0,
+            size: (max - min).max_element().max(f32::EPSILON),
+            up,
+            up_sign: 1.0,
+            forward: forward.0,
+            forward_sign: forward.1,
+            hand: Hand::Right,
+        });
+
+        Some(ModelId(self.models.len() - 1))
+    }
+}
+
+/// Dropped models are displayed on the selected object
+fn assign_to_selected(
+    id: ModelId,
+    selection: &selection::Selection,
+    repr_q: &mut Query<&mut repr::ReprSettings>,
+) {
+    if let Some(mut repr) = selection.selected.and_then(|ent| repr_q.get_mut(ent).ok()) {
+        repr.shape = Some(repr::Shape::Model(id));
+    }
+}
+
+fn system_load_dropped_models(
+    mut events: EventReader<FileDragAndDrop>,
+    mut library: ResMut<ModelLibrary>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    selection: Res<selection::Selection>,
+    mut repr_q: Query<&mut repr::ReprSettings>,
+) {
+    for event in events.read() {
+        if let FileDragAndDrop::DroppedFile { path_buf, .. } = event
+            && let Some(id) = library.load_file(&mut meshes, path_buf)
+        {
+            assign_to_selected(id, &selection, &mut repr_q);
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn system_load_dropped_web_files(
+    files: Res<web::DroppedFiles>,
+    mut library: ResMut<ModelLibrary>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    selection: Res<selection::Selection>,
+    mut repr_q: Query<&mut repr::ReprSettings>,
+) {
+    for (name, bytes) in files.take() {
+        if let Some(id) = library.load(&mut meshes, &name, &bytes, None) {
+            assign_to_selected(id, &selection, &mut repr_q);
+        }
+    }
+}
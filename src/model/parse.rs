@@ -0,0 +1,305 @@
+use std::path::Path;
+
+use base64::Engine;
+use bevy::prelude::*;
+
+/// Triangle soup collected from a model file
+pub struct Triangles {
+    pub positions: Vec<Vec3>,
+    /// `None` if at least one vertex didn't have a normal
+    pub normals: Option<Vec<Vec3>>,
+}
+
+impl Default for Triangles {
+    fn default() -> Self {
+        Self {
+            positions: Vec::new(),
+            normals: Some(Vec::new()),
+        }
+    }
+}
+
+impl Triangles {
+    fn push(&mut self, position: Vec3, normal: Option<Vec3>) {
+        self.positions.push(position);
+        match (&mut self.normals, normal) {
+            (Some(normals), Some(normal)) => normals.push(normal),
+            (normals, _) => *normals = None,
+        }
+    }
+}
+
+pub fn parse_obj(text: &str) -> Result<Triangles, String> {
+    let mut vertices = Vec::new();
+    let mut normals = Vec::new();
+    let mut triangles = Triangles::default();
+
+    // obj indices start at 1, negative indices are relative to the end of the list
+    let resolve = |index: &str, len: usize| -> Result<usize, String> {
+        let index = index
+            .parse::<isize>()
+            .map_err(|_| format!("invalid index: {index}"))?;
+        let resolved = if index < 0 {
+            len as isize + index
+        } else {
+            index - 1
+        };
+        if resolved < 0 || resolved as usize >= len {
+            return Err(format!("index out of range: {index}"));
+        }
+        Ok(resolved as usize)
+    };
+
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => vertices.push(parse_vec3(words)?),
+            Some("vn") => normals.push(parse_vec3(words)?),
+            Some("f") => {
+                let corners = words
+                    .map(|corner| {
+                        // v, v/vt, v//vn or v/vt/vn
+                        let mut parts = corner.split('/');
+                        let vertex = resolve(parts.next().unwrap_or_default(), vertices.len())?;
+                        let normal = match parts.nth(1) {
+                            Some(normal) if !normal.is_empty() => {
+                                Some(normals[resolve(normal, normals.len())?])
+                            }
+                            _ => None,
+                        };
+                        Ok((vertices[vertex], normal))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+
+                for i in 1..corners.len().saturating_sub(1) {
+                    for (position, normal) in [corners[0], corners[i], corners[i + 1]] {
+                        triangles.push(position, normal);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(triangles)
+}
+
+pub fn parse_stl(bytes: &[u8]) -> Result<Triangles, String> {
+    let mut triangles = Triangles {
+        normals: None,
+        ..default()
+    };
+
+    // computed in u64, a text header read as the triangle count overflows usize on 32 bit targets
+    let binary_len = bytes
+        .get(80..84)
+        .map(|count| 84 + 50 * u64::from(u32::from_le_bytes(count.try_into().unwrap())));
+
+    if binary_len == Some(bytes.len() as u64) {
+        let read_vec3 = |bytes: &[u8]| {
+            let mut values = bytes
+                .chunks_exact(4)
+                .map(|value| f32::from_le_bytes(value.try_into().unwrap()));
+            Vec3::new(
+                values.next().unwrap(),
+                values.next().unwrap(),
+                values.next().unwrap(),
+            )
+        };
+
+        // each triangle is a normal, three vertices and two bytes of attributes
+        for triangle in bytes[84..].chunks_exact(50) {
+            for vertex in 1..4 {
+                triangles.push(read_vec3(&triangle[vertex * 12..vertex * 12 + 12]), None);
+            }
+        }
+    } else {
+        let text = String::from_utf8_lossy(bytes);
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            if words.next() == Some("vertex") {
+                triangles.push(parse_vec3(words)?, None);
+            }
+        }
+
+        if !triangles.positions.len().is_multiple_of(3) {
+            return Err("number of vertices is not a multiple of 3".into());
+        }
+    }
+
+    Ok(triangles)
+}
+
+/// `base_dir` is used to resolve external buffers, which are not available when the file was dropped in the browser
+pub fn parse_gltf(bytes: &[u8], base_dir: Option<&Path>) -> Result<Triangles, String> {
+    let gltf = gltf::Gltf::from_slice(bytes).map_err(|err| err.to_string())?;
+
+    let buffers = gltf
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Bin => gltf
+                .blob
+                .clone()
+                .ok_or_else(|| "missing binary chunk".to_string()),
+            gltf::buffer::Source::Uri(uri) => {
+                if let Some(data) = uri.strip_prefix("data:") {
+                    let (_, encoded) = data
+                        .split_once(";base64,")
+                        .ok_or_else(|| "unsupported data uri".to_string())?;
+                    base64::engine::general_purpose::STANDARD
+                        .decode(encoded)
+                        .map_err(|err| err.to_string())
+                } else {
+                    let base_dir = base_dir.ok_or_else(|| {
+                        format!("external buffer {uri} can't be loaded, use .glb instead")
+                    })?;
+                    std::fs::read(base_dir.join(uri)).map_err(|err| format!("{uri}: {err}"))
+                }
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let scene = gltf
+        .default_scene()
+        .or_else(|| gltf.scenes().next())
+        .ok_or_else(|| "file contains no scenes".to_string())?;
+
+    let mut triangles = Triangles::default();
+    let mut nodes: Vec<_> = scene.nodes().map(|node| (node, Mat4::IDENTITY)).collect();
+
+    while let Some((node, parent_tf)) = nodes.pop() {
+        let tf = parent_tf * Mat4::from_cols_array_2d(&node.transform().matrix());
+        let normal_mat = Mat3::from_mat4(tf).inverse().transpose();
+
+        for primitive in node.mesh().iter().flat_map(|mesh| mesh.primitives()) {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let positions: Vec<_> = positions
+                .map(|pos| tf.transform_point3(pos.into()))
+                .collect();
+            let normals: Option<Vec<_>> = reader.read_normals().map(|normals| {
+                normals
+                    .map(|normal| (normal_mat * Vec3::from(normal)).normalize_or_zero())
+                    .collect()
+            });
+            let indices: Vec<_> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+                None => (0..positions.len()).collect(),
+            };
+
+            for i in indices {
+                let position = *positions
+                    .get(i)
+                    .ok_or_else(|| format!("index out of range: {i}"))?;
+                triangles.push(position, normals.as_ref().and_then(|normals| normals.get(i).copied()));
+            }
+        }
+
+        nodes.extend(node.children().map(|child| (child, tf)));
+    }
+
+    Ok(triangles)
+}
+
+fn parse_vec3<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Vec3, String> {
+    let mut next = || -> Result<f32, String> {
+        let word = words.next().ok_or_else(|| "not enough coordinates".to_string())?;
+        word.parse().map_err(|_| format!("invalid number: {word}"))
+    };
+    Ok(Vec3::new(next()?, next()?, next()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary_stl(triangles: &[[Vec3; 3]]) -> Vec<u8> {
+        let mut bytes = vec![0; 80];
+        bytes.extend((triangles.len() as u32).to_le_bytes());
+        for triangle in triangles {
+            for v in std::iter::once(&Vec3::Z).chain(triangle) {
+                for value in v.to_array() {
+                    bytes.extend(value.to_le_bytes());
+                }
+            }
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn stl_binary() {
+        let triangle = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        let parsed = parse_stl(&binary_stl(&[triangle, triangle])).unwrap();
+        assert_eq!(parsed.positions, [triangle, triangle].concat());
+        assert!(parsed.normals.is_none());
+    }
+
+    #[test]
+    fn stl_ascii() {
+        let text = "solid test with a long enough name to have the triangle count bytes in the header\n\
+            facet normal 0 0 1\n outer loop\n\
+            vertex 0 0 0\n vertex 1 0 0\n vertex 0 1 0\n\
+            endloop\n endfacet\nendsolid test\n";
+        let parsed = parse_stl(text.as_bytes()).unwrap();
+        assert_eq!(parsed.positions, [Vec3::ZERO, Vec3::X, Vec3::Y]);
+    }
+
+    #[test]
+    fn stl_ascii_incomplete_triangle() {
+        assert!(parse_stl(b"solid test\nvertex 0 0 0\nendsolid test\n").is_err());
+    }
+
+    #[test]
+    fn obj_quad_with_normals() {
+        let text = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1 -1//1\n";
+        let parsed = parse_obj(text).unwrap();
+        assert_eq!(parsed.positions, [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0), Vec3::Y]);
+        assert_eq!(parsed.normals, Some(vec![Vec3::Z; 6]));
+    }
+
+    #[test]
+    fn obj_without_normals() {
+        let parsed = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/2 3/3\n").unwrap();
+        assert_eq!(parsed.positions.len(), 3);
+        assert!(parsed.normals.is_none());
+    }
+
+    #[test]
+    fn obj_index_out_of_range() {
+        assert!(parse_obj("v 0 0 0\nf 1 2 3\n").is_err());
+    }
+
+    #[test]
+    fn gltf_embedded_buffer() {
+        let mut buffer = Vec::new();
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            buffer.extend(value.to_le_bytes());
+        }
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&buffer);
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": [{{ "mesh": 0, "translation": [0, 0, 2] }}],
+                "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }} }}] }}],
+                "accessors": [{{
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0, 0, 0], "max": [1, 1, 0]
+                }}],
+                "bufferViews": [{{ "buffer": 0, "byteLength": 36 }}],
+                "buffers": [{{ "byteLength": 36, "uri": "data:application/octet-stream;base64,{encoded}" }}]
+            }}"#
+        );
+        let parsed = parse_gltf(json.as_bytes(), None).unwrap();
+        assert_eq!(parsed.positions, [Vec3::new(0.0, 0.0, 2.0), Vec3::new(1.0, 0.0, 2.0), Vec3::new(0.0, 1.0, 2.0)]);
+        assert!(parsed.normals.is_none());
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+use wasm_bindgen::{JsCast, prelude::*};
+
+/// Files dropped onto the page. They are read asynchronously by the browser,
+/// so they are collected here and picked up by `system_load_dropped_web_files`
+#[derive(Resource, Clone, Default)]
+pub struct DroppedFiles(Arc<Mutex<Vec<(String, Vec<u8>)>>>);
+
+impl DroppedFiles {
+    pub fn take(&self) -> Vec<(String, Vec<u8>)> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+pub fn setup_drop_listener(files: Res<DroppedFiles>) {
+    let document = web_sys::window().unwrap().document().unwrap();
+
+    // the browser only allows dropping if dragover is cancelled
+    let on_dragover = Closure::<dyn Fn(web_sys::DragEvent)>::new(|event: web_sys::DragEvent| {
+        event.prevent_default();
+    });
+
+    let files = files.0.clone();
+    let on_drop = Closure::<dyn Fn(web_sys::DragEvent)>::new(move |event: web_sys::DragEvent| {
+        event.prevent_default();
+
+        let Some(list) = event.data_transfer().and_then(|transfer| transfer.files()) else {
+            return;
+        };
+
+        for file in (0..list.length()).filter_map(|i| list.get(i)) {
+            let files = files.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let Ok(buffer) = wasm_bindgen_futures::JsFuture::from(file.array_buffer()).await else {
+                    return;
+                };
+                let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+                files.lock().unwrap().push((file.name(), bytes));
+            });
+        }
+    });
+
+    document
+        .add_event_listener_with_callback("dragover", on_dragover.as_ref().unchecked_ref())
+        .unwrap();
+    document
+        .add_event_listener_with_callback("drop", on_drop.as_ref().unchecked_ref())
+        .unwrap();
+
+    // listeners stay for the lifetime of the page
+    on_dragover.forget();
+    on_drop.forget();
+}
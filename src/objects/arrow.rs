@@ -1,25 +1,47 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, render::view::RenderLayers};
+use bevy_rich_text3d as text3d;
 
 use crate::conversion as conv;
-use crate::{ repr, geometry };
+use crate::{ repr, geometry, model, selection };
 
 #[derive(Component)]
 #[require(
     ArrowIO,
-    repr::ReprSettings
+    repr::ReprSettings,
+    selection::Selected,
+    crate::group::InGroupDisplaySettings
 )]
 pub struct Arrow;
 
 #[derive(Component)]
-#[require(Transform, Visibility, geometry::UserTransform)]
+#[require(Transform, Visibility, geometry::UserTransform, geometry::ObjectCoordinates)]
 pub struct ArrowIO {
     pub pos: Vec3,
     pub quat: [String; 4],
     pub euler: Vec3,
+    /// the other Euler triple for the same rotation
+    pub euler_alt: Vec3,
     pub mat: [String; 9],
     pub tf_mat: [String; 16],
-    // pub look: [String; 3],
-    // pub up: Axis,
+    /// last displayed quaternion, for keeping the sign continuous
+    pub previous_quat: Quat,
+    /// display -q next to q
+    pub show_negated: bool,
+    /// how far the quaternion's norm can be from 1 before a warning is shown
+    pub norm_tolerance: f32,
+    pub show_unnormalized_axes: bool,
+    pub scale: Vec3,
+    pub look: geometry::LookTarget,
+    pub up: Vec3,
+    /// set if the last look-at target was parallel to `up`, to the up direction used instead
+    pub look_fallback: Option<geometry::LookUpFallback>,
+    pub arc_from: Vec3,
+    pub arc_to: Vec3,
+    /// side, up and forward directions
+    pub basis: [Vec3; 3],
+    /// user space axis for the swing-twist decomposition
+    pub twist_axis: Vec3,
+    pub show_twist_arc: bool,
 }
 
 impl Default for ArrowIO {
@@ -28,14 +50,30 @@ impl Default for ArrowIO {
             pos: default(),
             quat: default(),
             euler: default(),
+            euler_alt: default(),
             mat: default(),
             tf_mat: default(),
-            // look: default(),
-            // up: Axis::Y,
+            previous_quat: Quat::IDENTITY,
+            show_negated: false,
+            norm_tolerance: 1e-4,
+            show_unnormalized_axes: false,
+            scale: Vec3::ONE,
+            look: geometry::LookTarget::Direction(Vec3::X),
+            up: Vec3::Y,
+            look_fallback: None,
+            arc_from: Vec3::X,
+            arc_to: Vec3::Y,
+            basis: [Vec3::X, Vec3::Y, Vec3::NEG_Z],
+            twist_axis: Vec3::Y,
+            show_twist_arc: false,
         }
     }
 }
 
+/// Material of the main body of the displayed shape, as opposed to details like the triad or the outline
+#[derive(Component)]
+pub struct BodyMaterial(pub Handle<StandardMaterial>);
+
 #[derive(Default)]
 pub struct ArrowsCreatedCounter(usize);
 
@@ -52,19 +90,27 @@ pub fn system_init_arrow_names(
 }
 
 pub fn system_sync_arrow_io(
-    mut arrow_q: Query<
-        (&mut ArrowIO, Ref<geometry::UserTransform>),
-        Changed<geometry::UserTransform>,
-    >,
+    config_q: Query<Ref<crate::ui::ConfigIO>>,
+    mut arrow_q: Query<(&mut ArrowIO, Ref<geometry::UserTransform>)>,
 ) {
+    let config = config_q.single().unwrap();
+
     for (mut arrow, tf) in arrow_q.iter_mut() {
+        if !tf.is_changed() && !config.is_changed() {
+            continue;
+        }
+
         arrow.pos = tf.translation;
+        arrow.scale = tf.scale;
 
-        let quat = tf.rotation;
+        let quat = config.quat_sign.apply(tf.rotation, arrow.previous_quat);
+        arrow.previous_quat = quat;
         arrow.quat = conv::quat_to_strings(quat, conv::QuatStrMode::WXYZ);
 
         let (x, y, z) = quat.to_euler(EulerRot::XYZ);
-        arrow.euler = Vec3::new(x, y, z).map(f32::to_degrees);
+        let euler = Vec3::new(x, y, z).map(f32::to_degrees);
+        arrow.euler = euler.map(|v| config.euler_range.wrap(v));
+        arrow.euler_alt = crate::rotation::alternative_euler(euler).map(|v| config.euler_range.wrap(v));
 
         let mat = Mat3::from_quat(quat);
         arrow.mat = conv::mat3_to_strings(&mat, conv::MatStrMode::RowMajor);
@@ -74,26 +120,224 @@ pub fn system_sync_arrow_io(
     }
 }
 
+pub fn system_sync_arrow_visibility(
+    mut arrow_q: Query<(&repr::ComputedRepresentation, &mut Visibility), With<Arrow>>,
+) {
+    // solo is inherited, so looking at arrows is enough
+    let solo_active = arrow_q.iter().any(|(repr, _)| repr.solo);
+
+    for (repr, mut visibility) in arrow_q.iter_mut() {
+        let target = if repr.is_shown(solo_active) { Visibility::Inherited } else { Visibility::Hidden };
+        visibility.set_if_neq(target);
+    }
+}
+
+/// Twist around the chosen axis is drawn as an arc around the object's position
+pub fn system_draw_twist_arcs(
+    mut gizmos: Gizmos,
+    arrow_q: Query<(
+        &ArrowIO,
+        &Transform,
+        &geometry::UserTransform,
+        &geometry::ObjectCoordinates,
+        &repr::ComputedRepresentation,
+        &Visibility,
+    )>,
+) {
+    for (arrow, tf, utf, coord, repr, visibility) in arrow_q.iter() {
+        if !arrow.show_twist_arc || visibility == Visibility::Hidden {
+            continue;
+        }
+        let Some(decomposition) = crate::rotation::swing_twist(utf.rotation, arrow.twist_axis) else {
+            continue;
+        };
+
+        let axis = (coord.user2internal * arrow.twist_axis).normalize();
+        let angle = decomposition.twist_angle;
+
+        let radius = repr.length * 0.4;
+        let start = axis.any_orthonormal_vector() * radius;
+        let color = bevy::color::palettes::css::YELLOW;
+
+        let segments = 32;
+        let points = (0..=segments).map(|i| {
+            let rotation = Quat::from_axis_angle(axis, angle * i as f32 / segments as f32);
+            tf.translation + rotation * start
+        });
+        gizmos.linestrip(points, color);
+        gizmos.line(tf.translation, tf.translation + start, color);
+        gizmos.line(tf.translation, tf.translation + Quat::from_axis_angle(axis, angle) * start, color);
+        gizmos.line(tf.translation - axis * radius, tf.translation + axis * radius, color);
+    }
+}
+
+/// Axes of the matrix that an unnormalized quaternion turns into, which are scaled and skewed
+pub fn system_draw_unnormalized_axes(
+    mut gizmos: Gizmos,
+    arrow_q: Query<(
+        &ArrowIO,
+        &Transform,
+        &geometry::UserTransform,
+        &geometry::ObjectCoordinates,
+        &repr::ComputedRepresentation,
+        &Visibility,
+    )>,
+) {
+    use bevy::color::palettes::css;
+
+    for (arrow, tf, utf, coord, repr, visibility) in arrow_q.iter() {
+        if !arrow.show_unnormalized_axes || visibility == Visibility::Hidden {
+            continue;
+        }
+
+        let mat = Mat3::from_quat(geometry::convert_rotation(&coord.user2internal, utf.rotation));
+        for (axis, color) in [(mat.x_axis, css::RED), (mat.y_axis, css::LIME), (mat.z_axis, css::BLUE)] {
+            gizmos.line(tf.translation, tf.translation + axis * repr.length, color);
+        }
+    }
+}
+
+type XrayChangedFilter = (With<Arrow>, Or<(Changed<repr::ComputedRepresentation>, Changed<Children>)>);
+
+/// Render layers are not inherited, so every part of the displayed shape is moved to the x-ray layer
+pub fn system_sync_xray_layers(
+    mut cmd: Commands,
+    arrow_q: Query<(Entity, &repr::ComputedRepresentation), XrayChangedFilter>,
+    children_q: Query<&Children>,
+) {
+    for (ent, repr) in arrow_q.iter() {
+        let layers = if repr.xray {
+            RenderLayers::layer(repr::XRAY_LAYER)
+        } else {
+            RenderLayers::default()
+        };
+        for child in children_q.iter_descendants(ent) {
+            cmd.entity(child).insert(layers.clone());
+        }
+    }
+}
+
+type DisplayedArrowsQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        Ref<'static, repr::ComputedRepresentation>,
+        Ref<'static, selection::Selected>,
+        Ref<'static, geometry::ObjectCoordinates>,
+    ),
+    With<Arrow>,
+>;
+
 pub fn system_sync_display_arrow(
     mut cmd: Commands,
-    arrow_q: Query<
-        (Entity, &repr::ComputedRepresentation),
-        (With<Arrow>, Changed<repr::ComputedRepresentation>),
-    >,
+    arrow_q: DisplayedArrowsQuery,
+    models: Res<model::ModelLibrary>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    for (ent, repr) in arrow_q.iter() {
+    for (ent, repr, selected, coord) in arrow_q.iter() {
+        // the triad depends on the coordinate system, the arrow itself doesn't
+        let coord_changed = repr.triad && coord.is_changed();
+        let models_changed = matches!(repr.shape, repr::Shape::Model(_)) && models.is_changed();
+        if !repr.is_changed() && !selected.is_changed() && !coord_changed && !models_changed {
+            continue;
+        }
+
+        // shaded shapes need some brightness for the shading to be visible
+        let color = match repr.shape {
+            repr::Shape::Arrow => repr.color,
+            _ => repr.color.mix(&LinearRgba::WHITE, 0.3),
+        };
         let material = materials.add(StandardMaterial {
             depth_bias: -0.5,
             unlit: true,
-            ..Color::from(repr.color).into()
+            // model conventions may mirror the mesh
+            cull_mode: match repr.shape {
+                repr::Shape::Model(_) => None,
+                _ => Some(bevy::render::render_resource::Face::Back),
+            },
+            ..Color::from(color.with_alpha(repr.opacity)).into()
+        });
+
+        let details = (repr.shape != repr::Shape::Arrow).then(|| {
+            let unlit = |color: Color| StandardMaterial {
+                depth_bias: -0.5,
+                unlit: true,
+                ..color.with_alpha(repr.opacity).into()
+            };
+            let text_color = if repr.color.luminance() < 0.3 { Color::WHITE } else { Color::BLACK };
+
+            crate::mesh::ShapeDetails {
+                left: materials.add(unlit(bevy::color::palettes::css::RED.into())),
+                right: materials.add(unlit(bevy::color::palettes::css::LIME.into())),
+                text: materials.add(StandardMaterial {
+                    base_color_texture: Some(text3d::TextAtlas::DEFAULT_IMAGE.clone_weak()),
+                    base_color: text_color.with_alpha(repr.opacity),
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                }),
+            }
+        });
+
+        let outline_material = selected.0.then(|| {
+            materials.add(StandardMaterial {
+                depth_bias: -0.5,
+                unlit: true,
+                cull_mode: Some(bevy::render::render_resource::Face::Front),
+                ..Color::from(bevy::color::palettes::css::ORANGE).into()
+            })
+        });
+
+        let triad = repr.triad.then(|| {
+            geometry::Axis::all().map(|axis| {
+                let material = materials.add(StandardMaterial {
+                    depth_bias: -0.5,
+                    unlit: true,
+                    ..axis.color().with_alpha(repr.opacity).into()
+                });
+                let label = repr.triad_labels.then(|| {
+                    let text_material = materials.add(StandardMaterial {
+                        base_color_texture: Some(text3d::TextAtlas::DEFAULT_IMAGE.clone_weak()),
+                        base_color: axis.color().with_alpha(repr.opacity),
+                        alpha_mode: AlphaMode::Blend,
+                        unlit: true,
+                        cull_mode: None,
+                        ..default()
+                    });
+                    (axis.name(), text_material)
+                });
+                (coord.user2internal * axis.to_vec(), material, label)
+            })
         });
 
         cmd.entity(ent)
+            .insert(BodyMaterial(material.clone()))
             .despawn_related::<Children>()
             .with_children(|cmd| {
-                crate::mesh::spawn_arrow(&mut *meshes, cmd, repr.length, repr.scale, material);
+                let size = crate::mesh::ShapeSize { length: repr.length, radius_scale: repr.scale };
+                crate::mesh::spawn_shape(&mut meshes, cmd, &models, repr.shape, size, material, details.as_ref());
+
+                match (outline_material, repr.shape) {
+                    (None, _) => {}
+                    (Some(outline_material), repr::Shape::Arrow) => {
+                        cmd.spawn((crate::mesh::Outline, Transform::default(), Visibility::default()))
+                            .with_children(|cmd| {
+                                crate::mesh::spawn_arrow_outline(&mut meshes, cmd, size, outline_material);
+                            });
+                    }
+                    (Some(outline_material), shape) => {
+                        cmd.spawn((crate::mesh::Outline, Transform::from_scale(Vec3::splat(1.06)), Visibility::default()))
+                            .with_children(|cmd| {
+                                crate::mesh::spawn_shape(&mut meshes, cmd, &models, shape, size, outline_material, None);
+                            });
+                    }
+                }
+                let triad_size = crate::mesh::ShapeSize { length: repr.length * 0.5, ..size };
+                for (direction, material, label) in triad.into_iter().flatten() {
+                    crate::mesh::spawn_triad_axis(&mut meshes, cmd, direction, triad_size, material, label);
+                }
             });
     }
 }
@@ -4,16 +4,11 @@ use crate::repr;
 
 #[derive(Component)]
 #[require(
-    GroupIO,
-    repr::ReprSettings
+    repr::ReprSettings,
+    crate::selection::Selected
 )]
 pub struct Group;
 
-#[derive(Component, Default)]
-pub struct GroupIO {
-    pub selected_object: Option<Entity>,
-}
-
 #[derive(Component, Clone, Copy, PartialEq, Eq)]
 #[relationship(relationship_target = GroupedObjects)]
 pub struct InGroup(pub Entity);
@@ -22,6 +17,7 @@ pub struct InGroup(pub Entity);
 #[relationship_target(relationship = InGroup, linked_spawn)]
 pub struct GroupedObjects(Vec<Entity>);
 
+/// Arrows are displayed in the inspector, unless they are popped out into their own window
 #[derive(Component, Default, Clone, Copy)]
 pub struct InGroupDisplaySettings {
     pub popped_out: bool
@@ -1,5 +1,29 @@
     pub mod group;
     pub mod arrow;
+    pub mod probe;
 
     pub use group::Group;
     pub use arrow::Arrow;
+
+    use bevy::prelude::*;
+
+    /// Identifies an object across despawns, e.g. when a deleted object is restored by undo
+    #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub struct ObjectId(pub u64);
+
+    /// Arrows and groups, everything that can be put in a group
+    pub type IsObject = Or<(With<Arrow>, With<Group>)>;
+
+    #[derive(Default)]
+    pub struct ObjectsCreatedCounter(u64);
+
+    pub fn system_init_object_ids(
+        mut cmd: Commands,
+        objects_q: Query<Entity, (IsObject, Without<ObjectId>)>,
+        mut counter: Local<ObjectsCreatedCounter>,
+    ) {
+        for ent in objects_q.iter() {
+            counter.0 += 1;
+            cmd.entity(ent).insert(ObjectId(counter.0));
+        }
+    }
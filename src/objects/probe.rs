@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+
+use crate::geometry;
+
+/// A vector in user coordinates that is rotated (and optionally moved) by the arrow it's attached to
+#[derive(Component, Clone, Copy, PartialEq)]
+pub struct Probe {
+    pub vector: Vec3,
+    /// the vector is a point and is also moved by the arrow's position
+    pub translate: bool,
+}
+
+impl Default for Probe {
+    fn default() -> Self {
+        Self { vector: Vec3::X, translate: false }
+    }
+}
+
+pub struct ProbeResult {
+    /// the probe and its transformed counterpart in internal coordinates
+    pub before: Vec3,
+    pub after: Vec3,
+    /// the transformed probe in user coordinates
+    pub user: Vec3,
+}
+
+impl Probe {
+    /// Positions are converted without the rotated position mode, so that the result is in the unrotated user axes.
+    /// Unnormalized rotations are applied as the matrix the arrow is displayed with, scaling and skew included
+    pub fn apply(&self, coord: &geometry::CoordinateSystem, tf: &Transform) -> ProbeResult {
+        let before = coord.user2internal * self.vector / coord.positions_scale;
+        let mut after = Mat3::from_quat(tf.rotation) * before;
+        if self.translate {
+            after += tf.translation;
+        }
+        let user = coord.internal2user * after * coord.positions_scale;
+        ProbeResult { before, after, user }
+    }
+}
+
+/// Vectors start at the arrow's position, points at the origin
+pub fn system_draw_probes(
+    mut gizmos: Gizmos,
+    probe_q: Query<(&Probe, &Transform, &geometry::ObjectCoordinates, &Visibility)>,
+) {
+    use bevy::color::palettes::css;
+
+    for (probe, tf, coord, visibility) in probe_q.iter() {
+        if visibility == Visibility::Hidden {
+            continue;
+        }
+
+        let result = probe.apply(coord, tf);
+        let start = if probe.translate { Vec3::ZERO } else { tf.translation };
+        gizmos.arrow(start, start + result.before, css::GRAY);
+        gizmos.arrow(start, start + result.after, css::DARK_ORANGE);
+    }
+}
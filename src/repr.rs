@@ -3,42 +3,136 @@ use std::collections::VecDeque;
 use bevy::prelude::*;
 
 use crate::group::{InGroup, GroupedObjects};
+use crate::model::ModelId;
+
+/// Render layer of objects displayed in x-ray mode, it is drawn by a separate camera after everything else
+pub const XRAY_LAYER: usize = 1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Shape {
+    #[default]
+    Arrow,
+    Airplane,
+    Box,
+    Frustum,
+    Die,
+    /// mesh loaded from a file
+    Model(ModelId),
+}
+
+impl Shape {
+    pub fn all() -> [Self; 5] {
+        [Shape::Arrow, Shape::Airplane, Shape::Box, Shape::Frustum, Shape::Die]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shape::Arrow => "Arrow",
+            Shape::Airplane => "Airplane",
+            Shape::Box => "Box",
+            Shape::Frustum => "Camera frustum",
+            Shape::Die => "Die",
+            Shape::Model(_) => "Model",
+        }
+    }
+}
+
+/// Coordinate system that the values of objects are entered and displayed in
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Coordinates {
+    #[default]
+    Main,
+    /// one of the extra coordinate systems, the main one is used if it was deleted
+    Named(CoordinatesId),
+}
+
+/// Identifies one of the extra coordinate systems, so that groups keep using it after it's renamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CoordinatesId(pub u64);
 
 #[derive(Component, Clone)]
 pub struct ComputedRepresentation {
+    pub shape: Shape,
     pub color: LinearRgba,
     pub length: f32,
     pub scale: f32,
+    pub triad: bool,
+    pub triad_labels: bool,
+    pub visible: bool,
+    pub solo: bool,
+    pub locked: bool,
+    pub opacity: f32,
+    pub xray: bool,
+    pub coordinates: Coordinates,
+}
+
+impl ComputedRepresentation {
+    /// `solo_active` is true if any object in the scene is solo
+    pub fn is_shown(&self, solo_active: bool) -> bool {
+        self.visible && (self.solo || !solo_active)
+    }
 }
 
 impl Default for ComputedRepresentation {
     fn default() -> Self {
         ComputedRepresentation {
+            shape: Shape::Arrow,
             color: LinearRgba::BLACK,
             length: 1.0,
             scale: 1.0,
+            triad: false,
+            triad_labels: true,
+            visible: true,
+            solo: false,
+            locked: false,
+            opacity: 1.0,
+            xray: false,
+            coordinates: Coordinates::Main,
         }
     }
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Default, Clone, PartialEq)]
 #[require(ComputedRepresentation)]
 pub struct ReprSettings {
+    pub shape: Option<Shape>,
     pub color: Option<LinearRgba>,
     pub length: Option<f32>,
     pub scale: Option<f32>,
+    /// display all three local axes of the object in addition to the arrow
+    pub triad: Option<bool>,
+    pub triad_labels: Option<bool>,
+    pub visible: Option<bool>,
+    /// while any object is solo, all other objects are hidden
+    pub solo: Option<bool>,
+    /// locked objects can't be edited
+    pub locked: Option<bool>,
+    pub opacity: Option<f32>,
+    /// render on top of everything else, including the grid and other objects
+    pub xray: Option<bool>,
+    pub coordinates: Option<Coordinates>,
 }
 
 pub fn system_propagate_repr_settings(
     mut values_q: Query<(Entity, Ref<ReprSettings>, Mut<ComputedRepresentation>)>,
     hierarchy_q: Query<(Option<&InGroup>, Option<&GroupedObjects>), With<ComputedRepresentation>>,
+    moved_q: Query<Entity, Changed<InGroup>>,
 ) {
     let sync = |repr: &ReprSettings,
                 computed: &mut ComputedRepresentation,
                 parent_value: &ComputedRepresentation| {
+        computed.shape = repr.shape.unwrap_or(parent_value.shape);
         computed.color = repr.color.unwrap_or(parent_value.color);
         computed.length = repr.length.unwrap_or(parent_value.length);
         computed.scale = repr.scale.unwrap_or(parent_value.scale);
+        computed.triad = repr.triad.unwrap_or(parent_value.triad);
+        computed.triad_labels = repr.triad_labels.unwrap_or(parent_value.triad_labels);
+        computed.visible = repr.visible.unwrap_or(parent_value.visible);
+        computed.solo = repr.solo.unwrap_or(parent_value.solo);
+        computed.locked = repr.locked.unwrap_or(parent_value.locked);
+        computed.opacity = repr.opacity.unwrap_or(parent_value.opacity);
+        computed.xray = repr.xray.unwrap_or(parent_value.xray);
+        computed.coordinates = repr.coordinates.clone().unwrap_or_else(|| parent_value.coordinates.clone());
     };
 
     let mut queue = VecDeque::new();
@@ -48,6 +142,8 @@ pub fn system_propagate_repr_settings(
             queue.push_back(ent);
         }
     }
+    // objects moved to another group inherit from the new one
+    queue.extend(moved_q.iter());
 
     while let Some(ent) = queue.pop_front() {
         let (child_of, children) = hierarchy_q.get(ent).unwrap();
@@ -62,7 +158,7 @@ pub fn system_propagate_repr_settings(
         };
 
         if let Ok((_, repr, mut computed)) = values_q.get_mut(ent) {
-            sync(&*repr, &mut *computed, &parent_value);
+            sync(&repr, &mut computed, &parent_value);
             queue.extend(children.into_iter().flatten());
         }
     }
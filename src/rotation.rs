@@ -0,0 +1,388 @@
+// Constructing rotations from vectors given in user coordinates.
+// Everything is computed in internal coordinates, where the forward, up and side directions of objects are fixed,
+// and converted back to user rotations the same way as the rest of the values
+
+use bevy::prelude::*;
+
+use crate::geometry::{self, CoordinateSystem};
+
+const EPSILON: f32 = 1e-4;
+
+/// Directions of an object with the user rotation `rotation`, in user coordinates
+pub fn basis_of(coord: &CoordinateSystem, rotation: Quat) -> [Vec3; 3] {
+    let rotation = geometry::convert_rotation(&coord.user2internal, rotation);
+    [Vec3::X, Vec3::Y, Vec3::NEG_Z].map(|axis| coord.internal2user * (rotation * axis))
+}
+
+/// Shortest arc rotation that turns `from` into `to`, `None` if one of them is zero.
+/// The axis is arbitrary for opposite vectors
+pub fn rotation_arc(coord: &CoordinateSystem, from: Vec3, to: Vec3) -> Option<Quat> {
+    let from = (coord.user2internal * from).try_normalize()?;
+    let to = (coord.user2internal * to).try_normalize()?;
+    let rotation = Quat::from_rotation_arc(from, to);
+    Some(geometry::convert_rotation(&coord.internal2user, rotation))
+}
+
+/// Which of the two quaternions `q` and `-q`, that represent the same rotation, is displayed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum QuatSign {
+    #[default]
+    AsComputed,
+    /// w >= 0, for w = 0 the first non-zero component of the axis is positive
+    PositiveW,
+    /// the one closest to the previously displayed value, so that sequences of values don't flip
+    Continuous,
+    /// the one in the same hemisphere as the reference
+    Reference(Quat),
+}
+
+impl QuatSign {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QuatSign::AsComputed => "as computed",
+            QuatSign::PositiveW => "w ≥ 0",
+            QuatSign::Continuous => "continuous",
+            QuatSign::Reference(_) => "reference",
+        }
+    }
+
+    pub fn apply(self, quat: Quat, previous: Quat) -> Quat {
+        let flip = match self {
+            QuatSign::AsComputed => false,
+            QuatSign::PositiveW if quat.w == 0.0 => {
+                quat.xyz().to_array().into_iter().find(|&v| v != 0.0).is_some_and(|v| v < 0.0)
+            }
+            QuatSign::PositiveW => quat.w < 0.0,
+            QuatSign::Continuous => quat.dot(previous) < 0.0,
+            QuatSign::Reference(reference) => quat.dot(reference) < 0.0,
+        };
+        if flip { -quat } else { quat }
+    }
+}
+
+/// Range that displayed Euler angles are wrapped to
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AngleRange {
+    /// [-180, 180]
+    #[default]
+    Signed,
+    /// [0, 360)
+    Positive,
+}
+
+impl AngleRange {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AngleRange::Signed => "-180..180",
+            AngleRange::Positive => "0..360",
+        }
+    }
+
+    pub fn wrap(self, degrees: f32) -> f32 {
+        match self {
+            AngleRange::Signed if (-180.0..=180.0).contains(&degrees) => degrees,
+            AngleRange::Signed => (degrees + 180.0).rem_euclid(360.0) - 180.0,
+            AngleRange::Positive => degrees.rem_euclid(360.0),
+        }
+    }
+}
+
+/// The other Euler triple (in degrees) that gives the same rotation, valid for all Tait-Bryan orders
+pub fn alternative_euler(euler: Vec3) -> Vec3 {
+    Vec3::new(euler.x + 180.0, 180.0 - euler.y, euler.z + 180.0)
+}
+
+/// The middle angle (in degrees) is close enough to ±90° for the first and last axes to (almost) coincide
+pub fn is_gimbal_locked(euler: Vec3) -> bool {
+    const MARGIN_DEG: f32 = 5.0;
+    euler.y.to_radians().cos().abs() < MARGIN_DEG.to_radians().sin()
+}
+
+pub struct BasisRotation {
+    pub rotation: Quat,
+    /// the input had to be orthonormalized
+    pub adjusted: bool,
+    /// the input had the opposite handedness, the side direction was flipped
+    pub reflected: bool,
+}
+
+/// Rotation that turns the side, up and forward directions of an object into `basis`.
+/// Forward is kept as is, up is made perpendicular to it and side is made perpendicular to both
+pub fn rotation_from_basis(coord: &CoordinateSystem, basis: [Vec3; 3]) -> Option<BasisRotation> {
+    let [side, up, forward] = basis.map(|v| coord.user2internal * v);
+
+    let new_forward = forward.try_normalize()?;
+    let new_up = up.reject_from(new_forward).try_normalize()?;
+    let expected_side = new_forward.cross(new_up);
+    let new_side = side.reject_from(new_forward).reject_from(new_up);
+
+    let reflected = new_side.dot(expected_side) < 0.0;
+    let adjusted = !reflected
+        && [side, up, forward]
+            .iter()
+            .zip([expected_side, new_up, new_forward])
+            .any(|(v, new)| !v.abs_diff_eq(new, EPSILON));
+
+    let rotation = Quat::from_mat3(&Mat3::from_cols(expected_side, new_up, -new_forward));
+    Some(BasisRotation {
+        rotation: geometry::convert_rotation(&coord.internal2user, rotation),
+        adjusted,
+        reflected,
+    })
+}
+
+pub struct SwingTwist {
+    pub swing: Quat,
+    pub twist: Quat,
+    /// signed angle of the twist around the axis, in radians
+    pub twist_angle: f32,
+    /// unsigned angle of the swing, in radians
+    pub swing_angle: f32,
+}
+
+/// Splits `rotation` into `swing * twist`, where twist is a rotation around `axis`
+/// and swing is a rotation around an axis perpendicular to it.
+/// Works on the numeric values, so user rotations and axes can be used directly
+pub fn swing_twist(rotation: Quat, axis: Vec3) -> Option<SwingTwist> {
+    let axis = axis.try_normalize()?;
+    let projected = axis * rotation.xyz().dot(axis);
+
+    // rotation by 180 degrees around a perpendicular axis has no twist
+    let twist = projected
+        .extend(rotation.w)
+        .try_normalize()
+        .map_or(Quat::IDENTITY, Quat::from_vec4);
+    let swing = rotation * twist.inverse();
+
+    let twist_angle = 2.0 * twist.xyz().dot(axis).atan2(twist.w);
+    let twist_angle = if twist_angle > std::f32::consts::PI {
+        twist_angle - std::f32::consts::TAU
+    } else if twist_angle <= -std::f32::consts::PI {
+        twist_angle + std::f32::consts::TAU
+    } else {
+        twist_angle
+    };
+
+    let swing_angle = 2.0 * swing.w.abs().min(1.0).acos();
+
+    Some(SwingTwist { swing, twist, twist_angle, swing_angle })
+}
+
+pub struct MatrixCheck {
+    pub determinant: f32,
+    /// largest deviation of `M^T * M` from the identity
+    pub orthonormality_error: f32,
+}
+
+impl MatrixCheck {
+    pub fn is_orthonormal(&self) -> bool {
+        self.orthonormality_error < EPSILON
+    }
+
+    pub fn is_rotation(&self) -> bool {
+        self.determinant > 0.0 && self.is_orthonormal()
+    }
+}
+
+pub fn check_matrix(mat: Mat3) -> MatrixCheck {
+    let deviation = mat.transpose() * mat - Mat3::IDENTITY;
+    MatrixCheck {
+        determinant: mat.determinant(),
+        orthonormality_error: deviation.to_cols_array().into_iter().fold(0.0, |max, v| max.max(v.abs())),
+    }
+}
+
+/// Keeps the direction of the first column and the plane of the first two.
+/// The third column is recomputed, so reflections are turned into rotations by flipping it
+pub fn orthonormalize_gram_schmidt(mat: Mat3) -> Option<Mat3> {
+    let x = mat.x_axis.try_normalize()?;
+    let y = mat.y_axis.reject_from(x).try_normalize()?;
+    Some(Mat3::from_cols(x, y, x.cross(y)))
+}
+
+/// Closest rotation to the matrix, from its singular value decomposition `U * S * V^T`.
+/// For reflections the direction with the smallest singular value is flipped
+pub fn orthonormalize_polar(mat: Mat3) -> Option<Mat3> {
+    let (values, v) = symmetric_eigen(mat.transpose() * mat);
+    if values.x < EPSILON * EPSILON {
+        return None;
+    }
+
+    let u1 = (mat * v.x_axis).normalize();
+    let u2 = (mat * v.y_axis).reject_from(u1).try_normalize()?;
+    let u = Mat3::from_cols(u1, u2, u1.cross(u2));
+
+    let flip = (u.determinant() * v.determinant()).signum();
+    Some(u * Mat3::from_diagonal(Vec3::new(1.0, 1.0, flip)) * v.transpose())
+}
+
+/// Eigenvalues in decreasing order and the corresponding eigenvectors as columns,
+/// computed with Jacobi rotations
+fn symmetric_eigen(mat: Mat3) -> (Vec3, Mat3) {
+    let mut a = mat.to_cols_array_2d();
+    let mut v = Mat3::IDENTITY;
+
+    for _ in 0..32 {
+        let off_diagonal = a[1][0].abs() + a[2][0].abs() + a[2][1].abs();
+        if off_diagonal < 1e-12 {
+            break;
+        }
+
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[q][p].abs() < 1e-20 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[q][p]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+
+            let mut rotation = [[0.0; 3]; 3];
+            rotation[p][p] = c;
+            rotation[q][q] = c;
+            rotation[q][p] = s;
+            rotation[p][q] = -s;
+            let r = 3 - p - q;
+            rotation[r][r] = 1.0;
+
+            let rotation = Mat3::from_cols_array_2d(&rotation);
+            a = (rotation.transpose() * Mat3::from_cols_array_2d(&a) * rotation).to_cols_array_2d();
+            v *= rotation;
+        }
+    }
+
+    let mut order = [0, 1, 2];
+    order.sort_by(|&i, &j| a[j][j].total_cmp(&a[i][i]));
+    (
+        Vec3::from_array(order.map(|i| a[i][i])),
+        Mat3::from_cols(v.col(order[0]), v.col(order[1]), v.col(order[2])),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rotation(mat: Mat3) {
+        let check = check_matrix(mat);
+        assert!(check.orthonormality_error < 1e-5, "not orthonormal: {mat}");
+        assert!((check.determinant - 1.0).abs() < 1e-5, "determinant {}", check.determinant);
+    }
+
+    fn assert_same_rotation(a: Quat, b: Quat) {
+        assert!(a.dot(b).abs() > 1.0 - 1e-5, "{a} and {b} differ");
+    }
+
+    #[test]
+    fn polar_skewed() {
+        let rotation = Mat3::from_quat(Quat::from_euler(EulerRot::XYZ, 0.3, -1.2, 2.0));
+        let skewed = rotation * Mat3::from_cols(Vec3::X, Vec3::new(0.3, 1.0, 0.0), Vec3::new(-0.2, 0.1, 1.0));
+        assert_rotation(orthonormalize_polar(skewed).unwrap());
+    }
+
+    #[test]
+    fn polar_keeps_rotation() {
+        let rotation = Mat3::from_quat(Quat::from_euler(EulerRot::XYZ, 0.3, -1.2, 2.0));
+        let result = orthonormalize_polar(rotation).unwrap();
+        assert!(result.abs_diff_eq(rotation, 1e-5), "{result} != {rotation}");
+    }
+
+    #[test]
+    fn polar_reflection() {
+        let reflection = Mat3::from_diagonal(Vec3::new(1.0, 1.0, -1.0)) * Mat3::from_rotation_y(0.7);
+        assert_rotation(orthonormalize_polar(reflection).unwrap());
+    }
+
+    #[test]
+    fn polar_near_singular() {
+        let flat = Mat3::from_rotation_z(0.4) * Mat3::from_diagonal(Vec3::new(2.0, 1.0, 1e-3));
+        assert_rotation(orthonormalize_polar(flat).unwrap());
+    }
+
+    #[test]
+    fn polar_singular() {
+        // a flattened matrix still has a closest rotation, a line doesn't
+        assert_rotation(orthonormalize_polar(Mat3::from_diagonal(Vec3::new(1.0, 1.0, 0.0))).unwrap());
+        assert!(orthonormalize_polar(Mat3::from_diagonal(Vec3::new(1.0, 0.0, 0.0))).is_none());
+        assert!(orthonormalize_polar(Mat3::ZERO).is_none());
+    }
+
+    #[test]
+    fn gram_schmidt_keeps_first_column() {
+        let mat = Mat3::from_cols(Vec3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let result = orthonormalize_gram_schmidt(mat).unwrap();
+        assert_rotation(result);
+        assert!(result.x_axis.abs_diff_eq(Vec3::X, 1e-6));
+    }
+
+    #[test]
+    fn alternative_euler_same_rotation() {
+        for euler in [
+            Vec3::new(10.0, 20.0, 30.0),
+            Vec3::new(-170.0, 80.0, 179.0),
+            Vec3::new(180.0, -45.0, -180.0),
+            Vec3::new(30.0, 90.0, -60.0),
+            Vec3::new(0.0, -90.0, 0.0),
+        ] {
+            let alt = alternative_euler(euler);
+            let to_quat = |e: Vec3| {
+                let Vec3 { x, y, z } = e.map(f32::to_radians);
+                Quat::from_euler(EulerRot::XYZ, x, y, z)
+            };
+            assert_same_rotation(to_quat(euler), to_quat(alt));
+            // the alternative wrapped to either range is still the same rotation
+            for range in [AngleRange::Signed, AngleRange::Positive] {
+                assert_same_rotation(to_quat(euler), to_quat(alt.map(|v| range.wrap(v))));
+            }
+        }
+    }
+
+    #[test]
+    fn wrap_ranges() {
+        assert_eq!(AngleRange::Signed.wrap(180.0), 180.0);
+        assert_eq!(AngleRange::Signed.wrap(-180.0), -180.0);
+        assert_eq!(AngleRange::Signed.wrap(190.0), -170.0);
+        assert_eq!(AngleRange::Signed.wrap(-540.0), -180.0);
+        assert_eq!(AngleRange::Positive.wrap(-90.0), 270.0);
+        assert_eq!(AngleRange::Positive.wrap(360.0), 0.0);
+        assert_eq!(AngleRange::Positive.wrap(180.0), 180.0);
+    }
+
+    #[test]
+    fn gimbal_lock() {
+        assert!(is_gimbal_locked(Vec3::new(0.0, 90.0, 0.0)));
+        assert!(is_gimbal_locked(Vec3::new(0.0, -88.0, 0.0)));
+        assert!(is_gimbal_locked(Vec3::new(0.0, 270.0, 0.0)));
+        assert!(!is_gimbal_locked(Vec3::new(0.0, 80.0, 0.0)));
+        assert!(!is_gimbal_locked(Vec3::new(90.0, 0.0, 90.0)));
+    }
+
+    #[test]
+    fn quat_sign_positive_w() {
+        let quat = Quat::from_xyzw(0.0, 0.6, 0.0, -0.8);
+        assert_eq!(QuatSign::PositiveW.apply(quat, Quat::IDENTITY), -quat);
+        assert_eq!(QuatSign::PositiveW.apply(-quat, Quat::IDENTITY), -quat);
+        assert_eq!(QuatSign::AsComputed.apply(quat, Quat::IDENTITY), quat);
+    }
+
+    #[test]
+    fn quat_sign_zero_w() {
+        // a half turn, the first non-zero axis component decides
+        let quat = Quat::from_xyzw(0.0, -0.6, 0.8, 0.0);
+        assert_eq!(QuatSign::PositiveW.apply(quat, Quat::IDENTITY), -quat);
+        assert_eq!(QuatSign::PositiveW.apply(-quat, Quat::IDENTITY), -quat);
+    }
+
+    #[test]
+    fn quat_sign_continuous() {
+        let previous = Quat::from_rotation_z(179f32.to_radians());
+        // crossing 180° flips the sign of the computed quaternion
+        let quat = Quat::from_rotation_z(181f32.to_radians());
+        let result = QuatSign::Continuous.apply(quat, previous);
+        assert!(result.dot(previous) > 0.0);
+        assert_same_rotation(result, quat);
+
+        let reference = Quat::from_xyzw(0.0, 0.0, 0.0, -1.0);
+        assert!(QuatSign::Reference(reference).apply(Quat::IDENTITY, previous).w < 0.0);
+    }
+}
@@ -0,0 +1,482 @@
+// Objects are serialized as json, with transforms in user coordinates.
+// The same format is used for the clipboard, so objects can be moved between running instances
+
+use bevy::prelude::*;
+use bevy_egui::{EguiClipboard, EguiContexts};
+use serde_json::{Value, json};
+
+use crate::{
+    geometry::{self, CoordinateSystems, CoordinateSystemsParam, ObjectCoordinates, UserTransform},
+    group::{GroupedObjects, InGroup},
+    model::ModelLibrary,
+    objects::{Arrow, Group, IsObject, probe::Probe},
+    repr::{ComputedRepresentation, Coordinates, ReprSettings, Shape},
+    selection::Selection,
+    ui::{ConfigIO, ExtraCoordinateSystems},
+};
+
+const FORMAT_NAME: &str = "quaternions-offline";
+
+pub struct ScenePlugin;
+
+impl Plugin for ScenePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ObjectCommand>().add_systems(
+            Update,
+            (system_object_shortcuts, system_process_object_commands).chain(),
+        );
+    }
+}
+
+#[derive(Event, Clone, Copy)]
+pub enum ObjectCommand {
+    /// Copies the object into the same group
+    Duplicate(Entity),
+    Copy(Entity),
+    /// Pastes objects from the clipboard into the group
+    Paste(Entity),
+    /// Moves the object into another group, `group` can also be the top level
+    Move { ent: Entity, group: Entity },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Arrow,
+    Group,
+}
+
+/// Components of a single object, detached from the world.
+/// Shared by copied objects and history snapshots
+#[derive(Clone)]
+pub struct ObjectData {
+    pub kind: ObjectKind,
+    pub name: String,
+    /// user coordinates, only for arrows
+    pub transform: Option<Transform>,
+    pub repr: ReprSettings,
+    pub probe: Option<Probe>,
+}
+
+impl ObjectData {
+    pub fn new(
+        is_arrow: bool,
+        name: &Name,
+        repr: &ReprSettings,
+        utf: Option<&UserTransform>,
+        probe: Option<&Probe>,
+    ) -> Self {
+        Self {
+            kind: if is_arrow { ObjectKind::Arrow } else { ObjectKind::Group },
+            name: name.to_string(),
+            transform: utf.map(|utf| utf.0),
+            repr: repr.clone(),
+            probe: probe.copied(),
+        }
+    }
+}
+
+/// Object with everything in it
+#[derive(Deref, DerefMut)]
+pub struct SceneObject {
+    #[deref]
+    pub data: ObjectData,
+    pub children: Vec<SceneObject>,
+}
+
+impl SceneObject {
+    pub fn to_json(&self, models: &ModelLibrary, extra_coords: &ExtraCoordinateSystems) -> Value {
+        let mut object = json!({
+            "kind": match self.kind {
+                ObjectKind::Arrow => "arrow",
+                ObjectKind::Group => "group",
+            },
+            "name": self.name,
+            "repr": repr_to_json(&self.repr, models, extra_coords),
+        });
+        if let Some(tf) = &self.transform {
+            object["translation"] = json!(tf.translation.to_array());
+            object["rotation"] = json!(tf.rotation.to_array());
+            object["scale"] = json!(tf.scale.to_array());
+        }
+        if let Some(probe) = &self.probe {
+            object["probe"] = json!({ "vector": probe.vector.to_array(), "translate": probe.translate });
+        }
+        if !self.children.is_empty() {
+            object["children"] = self.children.iter().map(|child| child.to_json(models, extra_coords)).collect();
+        }
+
+        object
+    }
+
+    pub fn from_json(
+        value: &Value,
+        models: &ModelLibrary,
+        extra_coords: &ExtraCoordinateSystems,
+    ) -> Result<Self, String> {
+        let kind = match value["kind"].as_str() {
+            Some("arrow") => ObjectKind::Arrow,
+            Some("group") => ObjectKind::Group,
+            _ => return Err("unknown object kind".into()),
+        };
+
+        let transform = (kind == ObjectKind::Arrow).then(|| Transform {
+            translation: json_to_vec3(&value["translation"]).unwrap_or_default(),
+            rotation: json_to_array(&value["rotation"]).map_or(Quat::IDENTITY, Quat::from_array),
+            scale: json_to_vec3(&value["scale"]).unwrap_or(Vec3::ONE),
+        });
+
+        let probe = value.get("probe").filter(|_| kind == ObjectKind::Arrow).map(|probe| Probe {
+            vector: json_to_vec3(&probe["vector"]).unwrap_or(Vec3::X),
+            translate: probe["translate"].as_bool().unwrap_or_default(),
+        });
+
+        let children: Vec<SceneObject> = match value.get("children") {
+            Some(Value::Array(children)) if kind == ObjectKind::Group => children
+                .iter()
+                .map(|child| SceneObject::from_json(child, models, extra_coords))
+                .collect::<Result<_, _>>()?,
+            _ => Vec::new(),
+        };
+
+        Ok(SceneObject {
+            data: ObjectData {
+                kind,
+                name: value["name"].as_str().unwrap_or_default().to_string(),
+                transform,
+                repr: repr_from_json(&value["repr"], models, extra_coords),
+                probe,
+            },
+            children,
+        })
+    }
+}
+
+pub fn objects_to_string(
+    objects: &[SceneObject],
+    models: &ModelLibrary,
+    extra_coords: &ExtraCoordinateSystems,
+) -> String {
+    let objects: Vec<_> = objects.iter().map(|object| object.to_json(models, extra_coords)).collect();
+    json!({ "format": FORMAT_NAME, "objects": objects }).to_string()
+}
+
+pub fn objects_from_str(
+    text: &str,
+    models: &ModelLibrary,
+    extra_coords: &ExtraCoordinateSystems,
+) -> Result<Vec<SceneObject>, String> {
+    let value: Value = serde_json::from_str(text).map_err(|err| err.to_string())?;
+    if value["format"].as_str() != Some(FORMAT_NAME) {
+        return Err("not a scene".into());
+    }
+
+    value["objects"]
+        .as_array()
+        .ok_or_else(|| "missing objects".to_string())?
+        .iter()
+        .map(|object| SceneObject::from_json(object, models, extra_coords))
+        .collect()
+}
+
+/// Extra coordinate systems are referred to by name, like models, so that objects can be moved between instances
+fn repr_to_json(repr: &ReprSettings, models: &ModelLibrary, extra_coords: &ExtraCoordinateSystems) -> Value {
+    let mut value = json!({});
+    match repr.shape {
+        // model ids are only valid in this instance
+        Some(Shape::Model(id)) => {
+            if let Some(model) = models.get(id) {
+                value["shape"] = json!({ "model": model.name });
+            }
+        }
+        Some(shape) => value["shape"] = json!(shape.name()),
+        None => {}
+    }
+    if let Some(color) = repr.color {
+        value["color"] = json!(color.to_f32_array());
+    }
+    if let Some(length) = repr.length {
+        value["length"] = json!(length);
+    }
+    if let Some(scale) = repr.scale {
+        value["scale"] = json!(scale);
+    }
+    if let Some(triad) = repr.triad {
+        value["triad"] = json!(triad);
+    }
+    if let Some(triad_labels) = repr.triad_labels {
+        value["triad_labels"] = json!(triad_labels);
+    }
+    if let Some(visible) = repr.visible {
+        value["visible"] = json!(visible);
+    }
+    if let Some(solo) = repr.solo {
+        value["solo"] = json!(solo);
+    }
+    if let Some(locked) = repr.locked {
+        value["locked"] = json!(locked);
+    }
+    if let Some(opacity) = repr.opacity {
+        value["opacity"] = json!(opacity);
+    }
+    if let Some(xray) = repr.xray {
+        value["xray"] = json!(xray);
+    }
+    match &repr.coordinates {
+        Some(Coordinates::Main) => value["coordinates"] = json!("main"),
+        Some(Coordinates::Named(id)) => {
+            if let Some(coords) = extra_coords.get(*id) {
+                value["coordinates"] = json!({ "named": coords.name });
+            }
+        }
+        None => {}
+    }
+    value
+}
+
+/// Unknown or missing values are left to be inherited
+fn repr_from_json(value: &Value, models: &ModelLibrary, extra_coords: &ExtraCoordinateSystems) -> ReprSettings {
+    let shape = match &value["shape"] {
+        Value::String(name) => Shape::all().into_iter().find(|shape| shape.name() == name),
+        Value::Object(shape) => shape
+            .get("model")
+            .and_then(Value::as_str)
+            .and_then(|name| models.iter().find(|(_, model)| model.name == name))
+            .map(|(id, _)| Shape::Model(id)),
+        _ => None,
+    };
+
+    ReprSettings {
+        shape,
+        color: json_to_array(&value["color"]).map(LinearRgba::from_f32_array),
+        length: value["length"].as_f64().map(|length| length as f32),
+        scale: value["scale"].as_f64().map(|scale| scale as f32),
+        triad: value["triad"].as_bool(),
+        triad_labels: value["triad_labels"].as_bool(),
+        visible: value["visible"].as_bool(),
+        solo: value["solo"].as_bool(),
+        locked: value["locked"].as_bool(),
+        opacity: value["opacity"].as_f64().map(|opacity| opacity as f32),
+        xray: value["xray"].as_bool(),
+        coordinates: match &value["coordinates"] {
+            Value::String(main) if main == "main" => Some(Coordinates::Main),
+            Value::Object(coordinates) => coordinates
+                .get("named")
+                .and_then(Value::as_str)
+                .and_then(|name| extra_coords.find_name(name))
+                .map(|coords| Coordinates::Named(coords.id)),
+            _ => None,
+        },
+    }
+}
+
+fn json_to_array<const N: usize>(value: &Value) -> Option<[f32; N]> {
+    let values = value.as_array()?;
+    let mut array = [0.0; N];
+    for (to, from) in array.iter_mut().zip(values) {
+        *to = from.as_f64()? as f32;
+    }
+    (values.len() == N).then_some(array)
+}
+
+fn json_to_vec3(value: &Value) -> Option<Vec3> {
+    json_to_array(value).map(Vec3::from_array)
+}
+
+fn system_object_shortcuts(
+    mut ctx: EguiContexts,
+    keys: Res<ButtonInput<KeyCode>>,
+    selection: Res<Selection>,
+    config_q: Query<Entity, With<ConfigIO>>,
+    in_group_q: Query<&InGroup>,
+    mut events: EventWriter<ObjectCommand>,
+) {
+    if let Ok(ctx) = ctx.ctx_mut()
+        && ctx.wants_keyboard_input()
+    {
+        return;
+    }
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    if let Some(selected) = selection.selected {
+        if keys.just_pressed(KeyCode::KeyC) {
+            events.write(ObjectCommand::Copy(selected));
+        }
+        if keys.just_pressed(KeyCode::KeyD) {
+            events.write(ObjectCommand::Duplicate(selected));
+        }
+    }
+
+    // pasted next to the selected object, or at the top level
+    if keys.just_pressed(KeyCode::KeyV) {
+        let group = selection
+            .selected
+            .and_then(|selected| in_group_q.get(selected).ok())
+            .map_or_else(|| config_q.single().unwrap(), |in_group| in_group.0);
+        events.write(ObjectCommand::Paste(group));
+    }
+}
+
+type SceneObjectsQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Name,
+        Has<Arrow>,
+        &'static ReprSettings,
+        Option<&'static UserTransform>,
+        Option<&'static Probe>,
+        Option<&'static GroupedObjects>,
+        Option<&'static InGroup>,
+    ),
+    IsObject,
+>;
+
+fn system_process_object_commands(
+    mut cmd: Commands,
+    mut events: EventReader<ObjectCommand>,
+    mut clip: ResMut<EguiClipboard>,
+    models: Res<ModelLibrary>,
+    systems: CoordinateSystemsParam,
+    computed_q: Query<&ComputedRepresentation>,
+    objects_q: SceneObjectsQuery,
+) {
+    let systems = systems.get();
+    // objects are spawned in the coordinate system of the group they are added to
+    let group_coordinates = |group: Entity| {
+        computed_q
+            .get(group)
+            .map_or(Coordinates::Main, |computed| computed.coordinates.clone())
+    };
+
+    let collect = |ent: Entity| collect_object(ent, &objects_q);
+    // nothing can be added to or removed from locked groups
+    let is_locked = |ent: Entity| computed_q.get(ent).is_ok_and(|computed| computed.locked);
+
+    for event in events.read() {
+        match *event {
+            ObjectCommand::Duplicate(ent) => {
+                let (Some(mut object), Ok((.., Some(in_group)))) = (collect(ent), objects_q.get(ent)) else {
+                    continue;
+                };
+                if is_locked(in_group.0) {
+                    continue;
+                }
+                object.name += " (copy)";
+                spawn_object(&mut cmd, &systems, &group_coordinates(in_group.0), in_group.0, &object);
+            }
+
+            ObjectCommand::Copy(ent) => {
+                if let Some(object) = collect(ent) {
+                    clip.set_text(&objects_to_string(&[object], &models, systems.extra));
+                }
+            }
+
+            ObjectCommand::Paste(group) => {
+                if is_locked(group) {
+                    continue;
+                }
+                let text = clip.get_text().unwrap_or_default();
+                match objects_from_str(&text, &models, systems.extra) {
+                    Ok(objects) => {
+                        for object in &objects {
+                            spawn_object(&mut cmd, &systems, &group_coordinates(group), group, object);
+                        }
+                    }
+                    Err(err) => warn!("clipboard doesn't contain objects: {err}"),
+                }
+            }
+
+            ObjectCommand::Move { ent, group } => {
+                // a group can't be moved into itself or anything inside of it
+                let mut parent = Some(group);
+                while let Some(current) = parent {
+                    if current == ent {
+                        break;
+                    }
+                    parent = objects_q
+                        .get(current)
+                        .ok()
+                        .and_then(|(.., in_group)| in_group)
+                        .map(|in_group| in_group.0);
+                }
+
+                if parent.is_none()
+                    && !is_locked(ent)
+                    && !is_locked(group)
+                    && let Ok((.., in_group)) = objects_q.get(ent)
+                    && in_group != Some(&InGroup(group))
+                    && !in_group.is_some_and(|in_group| is_locked(in_group.0))
+                {
+                    cmd.entity(ent).insert(InGroup(group));
+                }
+            }
+        }
+    }
+}
+
+fn collect_object(
+    ent: Entity,
+    objects_q: &SceneObjectsQuery,
+) -> Option<SceneObject> {
+    let (name, is_arrow, repr, utf, probe, grouped, _) = objects_q.get(ent).ok()?;
+
+    Some(SceneObject {
+        data: ObjectData::new(is_arrow, name, repr, utf, probe),
+        children: grouped
+            .into_iter()
+            .flatten()
+            .filter_map(|child| collect_object(child, objects_q))
+            .collect(),
+    })
+}
+
+/// Spawns the object with everything in it into `group`.
+/// `coordinates` are inherited from the group, transforms are converted from the coordinate system they resolve to
+pub fn spawn_object(
+    cmd: &mut Commands,
+    systems: &CoordinateSystems,
+    coordinates: &Coordinates,
+    group: Entity,
+    object: &SceneObject,
+) -> Entity {
+    let mut ent = cmd.spawn((Name::new(object.name.clone()), object.repr.clone(), InGroup(group)));
+    let coordinates = object.repr.coordinates.as_ref().unwrap_or(coordinates);
+
+    match object.kind {
+        ObjectKind::Arrow => {
+            let coord = systems.resolve(coordinates);
+            let utf = object.transform.unwrap_or_default();
+            let rotation = geometry::convert_rotation(&coord.user2internal, utf.rotation);
+            let translation = geometry::convert_position_u2i(
+                &coord.user2internal,
+                coord.positions_scale,
+                coord.position_mode,
+                rotation,
+                utf.translation,
+            );
+            ent.insert((
+                Arrow,
+                Transform {
+                    translation,
+                    rotation,
+                    scale: geometry::convert_scale(&coord.user2internal, utf.scale),
+                },
+                ObjectCoordinates(coord),
+            ));
+            if let Some(probe) = object.probe {
+                ent.insert(probe);
+            }
+        }
+        ObjectKind::Group => {
+            ent.insert(Group);
+        }
+    }
+
+    let ent = ent.id();
+    for child in &object.children {
+        spawn_object(cmd, systems, coordinates, ent, child);
+    }
+    ent
+}
@@ -0,0 +1,105 @@
+use bevy::ecs::system::SystemParam;
+use bevy::picking::mesh_picking::ray_cast::{MeshRayCast, MeshRayCastSettings};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::EguiContexts;
+
+use crate::MainCamera;
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>().add_systems(
+            Update,
+            (system_pick_objects, system_sync_selected).chain(),
+        );
+    }
+}
+
+/// Object that is currently selected, either by clicking it in the viewport or in the ui
+#[derive(Resource, Default)]
+pub struct Selection {
+    pub selected: Option<Entity>,
+}
+
+/// Mirrors `Selection` on the object itself, so that its display can react to being selected
+#[derive(Component, Default, PartialEq, Eq)]
+pub struct Selected(pub bool);
+
+/// Ray from the main camera through the mouse cursor
+#[derive(SystemParam)]
+struct CursorRay<'w, 's> {
+    window_q: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    camera_q: Query<'w, 's, (&'static Camera, &'static GlobalTransform), With<MainCamera>>,
+}
+
+impl CursorRay<'_, '_> {
+    fn get(&self) -> Option<Ray3d> {
+        let cursor = self.window_q.single().ok()?.cursor_position()?;
+        let (camera, camera_tf) = self.camera_q.single().unwrap();
+        camera.viewport_to_world(camera_tf, cursor).ok()
+    }
+}
+
+fn system_pick_objects(
+    mut ctx: EguiContexts,
+    mut ray_cast: MeshRayCast,
+    mut selection: ResMut<Selection>,
+    input_mouse: Res<ButtonInput<MouseButton>>,
+    cursor_ray: CursorRay,
+    selectable_q: Query<(), With<Selected>>,
+    parents_q: Query<&ChildOf>,
+) {
+    if !input_mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    // clicks on egui windows should not go through to the viewport
+    if let Ok(ctx) = ctx.ctx_mut()
+        && ctx.is_pointer_over_area()
+    {
+        return;
+    }
+
+    let Some(ray) = cursor_ray.get() else {
+        return;
+    };
+
+    // meshes are spawned as children of the object they display
+    let find_object = |ent: Entity| {
+        std::iter::once(ent)
+            .chain(parents_q.iter_ancestors(ent))
+            .find(|&ent| selectable_q.contains(ent))
+    };
+    let filter = |ent: Entity| find_object(ent).is_some();
+    let settings = MeshRayCastSettings::default().with_filter(&filter);
+
+    let picked = ray_cast
+        .cast_ray(ray, &settings)
+        .first()
+        .and_then(|(ent, _)| find_object(*ent));
+
+    if selection.selected != picked {
+        selection.selected = picked;
+    }
+}
+
+fn system_sync_selected(
+    mut selection: ResMut<Selection>,
+    mut selected_q: Query<(Entity, &mut Selected)>,
+) {
+    if let Some(selected) = selection.selected
+        && !selected_q.contains(selected)
+    {
+        selection.selected = None;
+    }
+
+    if !selection.is_changed() {
+        return;
+    }
+
+    for (ent, mut selected) in selected_q.iter_mut() {
+        selected.set_if_neq(Selected(selection.selected == Some(ent)));
+    }
+}
@@ -1,28 +1,37 @@
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiClipboard};
 
-use crate::arrow::ArrowIO;
+use crate::{arrow::ArrowIO, probe::Probe, geometry::{self, Axis, CoordinateSystem, LookTarget, TransformDecomposition}, rotation};
 use super::{*, common::*};
 
 pub fn arrow_ui(
     cmd: &mut Commands,
     ui: &mut egui::Ui,
     clip: &mut EguiClipboard,
-    ArrowsQueryItem { ent, mut name, mut arrow, mut repr, computed, in_group_display, .. }: ArrowsQueryItem,
-    mut events: &mut EventWriter<ApplyTransformCommand>,
+    InspectorContext { models, main_coord, extra_coords, targets }: &InspectorContext,
+    ArrowsQueryItem { ent, mut name, mut arrow, mut repr, computed, in_group_display, user_tf, tf, coords, probe, .. }: ArrowsQueryItem,
+    InspectorEvents { transforms: events, objects: object_events }: &mut InspectorEvents,
 ) {
     ui.horizontal(|ui| {
         if let Some(mut igd) = in_group_display {
-            let text = if igd.popped_out { "Return to group" } else { "Pop out" };
+            let text = if igd.popped_out { "Return to inspector" } else { "Pop out" };
             if ui.button(text).clicked() {
                 igd.popped_out = !igd.popped_out;
             }
         }
 
-        if ui.button("Delete").clicked() {
+        if ui.add_enabled(!computed.locked, egui::Button::new("Delete")).clicked() {
             cmd.entity(ent).despawn();
         }
 
+        if ui.button("Duplicate").clicked() {
+            object_events.write(ObjectCommand::Duplicate(ent));
+        }
+
+        if ui.button("Copy").clicked() {
+            object_events.write(ObjectCommand::Copy(ent));
+        }
+
         if ui.button("Reset input fields").clicked() {
             // sync_objects will set input fields to the current values
             events.write(ApplyTransformCommand::recompute(ent));
@@ -32,25 +41,39 @@ pub fn arrow_ui(
     ui.collapsing("Settings", |ui| {
         ui.horizontal(|ui| {
             ui.label("Name: ");
-            name.mutate(|name| {
-                ui.add(egui::TextEdit::singleline(name).desired_width(100.0));
+            ui.add_enabled_ui(!computed.locked, |ui| {
+                name_edit_ui(ui, &mut name);
             });
         });
 
-        if repr_settings::repr_settings_ui(false, ui, repr.bypass_change_detection(), &*computed) {
+        // stays enabled for locked objects, so that they can be unlocked
+        if repr_settings::repr_settings_ui(false, ui, models, repr.bypass_change_detection(), &*computed) {
             repr.set_changed();
         }
     });
 
+    if computed.locked {
+        ui.label("Locked, unlock it in the settings to edit");
+    }
+
     egui::CollapsingHeader::new("Values")
         .default_open(true)
         .show(ui, |ui| {
-            display_position(ui, &mut *clip, ent, &mut arrow, &mut events);
-            display_quaternion(ui, &mut *clip, ent, &mut arrow, &mut events);
-            display_euler(ui, &mut *clip, ent, &mut arrow, &mut events);
-            display_matrix(ui, &mut *clip, ent, &mut arrow, &mut events);
-            display_transform_matrix(ui, &mut *clip, ent, &mut arrow, &mut events);
-            // display_look(ui, &mut *clip, ent, &*coord, &mut arrow, tf.reborrow());
+            if computed.locked {
+                ui.disable();
+            }
+            display_position(ui, &mut *clip, ent, &mut arrow, events);
+            display_quaternion(ui, &mut *clip, ent, &mut arrow, events);
+            display_euler(ui, &mut *clip, ent, &mut arrow, events);
+            display_matrix(ui, &mut *clip, ent, &mut arrow, events);
+            display_transform_matrix(ui, &mut *clip, ent, &mut arrow, events);
+            display_scale(ui, ent, &mut arrow, events);
+            display_coordinate_systems(ui, ent, coords, main_coord, extra_coords, tf, events);
+            display_look(ui, ent, &mut arrow, targets, events);
+            display_from_to(ui, ent, coords, &mut arrow, events);
+            display_basis(ui, ent, coords, &mut arrow, events);
+            display_swing_twist(ui, clip, user_tf.rotation, &mut arrow);
+            display_probe(cmd, ui, clip, ent, coords, tf, probe);
         });
 }
 
@@ -123,6 +146,30 @@ fn display_quaternion(
                 display_field(ui, "Y", &mut arrow.quat[2]);
                 display_field(ui, "Z", &mut arrow.quat[3]);
             });
+
+        let quat = conv::strings_to_quat(&arrow.quat, conv::QuatStrMode::WXYZ);
+        let angle = 2.0 * (quat.w / quat.length()).clamp(-1.0, 1.0).acos().to_degrees();
+        if quat.length() == 0.0 {
+            ui.colored_label(ui.visuals().error_fg_color, "Zero quaternion");
+        } else if quat.w >= 0.0 {
+            ui.label(format!("Hemisphere w ≥ 0, rotation by {angle:.2}°"));
+        } else {
+            ui.label(format!("Hemisphere w < 0, rotation by {angle:.2}°, same as {:.2}° the other way", 360.0 - angle));
+        }
+
+        display_norm(ui, quat, arrow);
+
+        ui.checkbox(&mut arrow.show_negated, "Show -q");
+        if arrow.show_negated {
+            let negated = conv::quat_to_strings(-quat, conv::QuatStrMode::WXYZ);
+            ui.horizontal(|ui| {
+                ui.label(format!("-q: {}", negated.join(", ")));
+                if ui.button("Copy").clicked() {
+                    clip_copy(clip, &negated);
+                }
+            });
+        }
+
         if ui.button("Apply").clicked() {
             events.write(ApplyTransformCommand::rot_quat(
                 ent,
@@ -147,6 +194,40 @@ fn display_quaternion(
     });
 }
 
+/// A quaternion q with norm s scales vectors by s² in the sandwich product q v q*,
+/// while the rotation matrix formula gives s²R + (1 - s²)I, which also skews
+fn display_norm(ui: &mut egui::Ui, quat: Quat, arrow: &mut ArrowIO) {
+    let norm = quat.length();
+    ui.label(format!("Norm: {norm:.6}"));
+
+    ui.horizontal(|ui| {
+        ui.label("Tolerance");
+        let widget = egui::DragValue::new(&mut arrow.norm_tolerance).speed(1e-5).range(0.0..=1.0).max_decimals(6);
+        ui.add(widget);
+    });
+
+    if norm == 0.0 || (norm - 1.0).abs() <= arrow.norm_tolerance {
+        return;
+    }
+
+    ui.colored_label(
+        ui.visuals().warn_fg_color,
+        format!("The quaternion is not normalized, off by {:.6}", norm - 1.0),
+    );
+    ui.label(format!("q v q* scales vectors by {:.6}", norm * norm));
+
+    let mat = Mat4::from_mat3(Mat3::from_quat(quat));
+    if let Some(parts) = TransformDecomposition::new(mat) {
+        ui.label(format!(
+            "As a matrix: scale {:.6}, {:.6}, {:.6}, shear XY {:.6}, XZ {:.6}, YZ {:.6}",
+            parts.scale.x, parts.scale.y, parts.scale.z, parts.shear.x, parts.shear.y, parts.shear.z,
+        ));
+    } else {
+        ui.colored_label(ui.visuals().error_fg_color, "As a matrix it's degenerate");
+    }
+    ui.checkbox(&mut arrow.show_unnormalized_axes, "Show the matrix axes");
+}
+
 fn display_euler(
     ui: &mut egui::Ui,
     clip: &mut EguiClipboard,
@@ -183,6 +264,21 @@ fn display_euler(
                 changed = true;
             }
         });
+
+        if rotation::is_gimbal_locked(arrow.euler) {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "Gimbal lock: Y is close to ±90°, X and Z rotate around almost the same axis and only their sum or difference is meaningful",
+            );
+        }
+
+        let alt = arrow.euler_alt;
+        ui.horizontal(|ui| {
+            ui.label(format!("Alternative: X {:.2}°, Y {:.2}°, Z {:.2}°", alt.x, alt.y, alt.z));
+            if ui.button("Copy").clicked() {
+                clip_copy(clip, &conv::vec_to_strings(alt));
+            }
+        });
     });
 
     if changed {
@@ -228,11 +324,39 @@ fn display_matrix(
                 ui.end_row();
             });
 
+        let mat = conv::strings_to_mat3(&arrow.mat, conv::MatStrMode::RowMajor);
+        let check = rotation::check_matrix(mat);
+        ui.label(format!("Determinant: {:.6}", check.determinant));
+        ui.label(format!("Orthonormality error: {:.6}", check.orthonormality_error));
+        if check.determinant.abs() < 1e-6 {
+            ui.colored_label(ui.visuals().error_fg_color, "The matrix is degenerate");
+        } else if check.determinant < 0.0 {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "Reflection (negative determinant), this flips handedness and is not a rotation",
+            );
+        } else if !check.is_rotation() {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "Not orthonormal, orthonormalize it before applying",
+            );
+        }
+
+        ui.horizontal(|ui| {
+            let mut orthonormalized = None;
+            if ui.button("Gram-Schmidt").on_hover_text("Orthonormalize, keeping the first column").clicked() {
+                orthonormalized = rotation::orthonormalize_gram_schmidt(mat);
+            }
+            if ui.button("Polar").on_hover_text("Orthonormalize to the closest rotation").clicked() {
+                orthonormalized = rotation::orthonormalize_polar(mat);
+            }
+            if let Some(mat) = orthonormalized {
+                arrow.mat = conv::mat3_to_strings(&mat, conv::MatStrMode::RowMajor);
+            }
+        });
+
         if ui.button("Apply").clicked() {
-            events.write(ApplyTransformCommand::rot_mat(
-                ent,
-                conv::strings_to_mat3(&arrow.mat, conv::MatStrMode::RowMajor),
-            ));
+            events.write(ApplyTransformCommand::rot_mat(ent, mat));
         }
 
         egui::Grid::new(ent.index().to_string() + "mat_io")
@@ -303,11 +427,11 @@ fn display_transform_matrix(
                 ui.end_row();
             });
 
+        let mat = conv::strings_to_mat4(&arrow.tf_mat, conv::MatStrMode::RowMajor);
+        display_decomposition(ui, mat, arrow);
+
         if ui.button("Apply").clicked() {
-            events.write(ApplyTransformCommand::tf_mat(
-                ent,
-                conv::strings_to_mat4(&arrow.tf_mat, conv::MatStrMode::RowMajor),
-            ));
+            events.write(ApplyTransformCommand::tf_mat(ent, mat));
         }
 
         egui::Grid::new(ent.index().to_string() + "mat_io")
@@ -334,3 +458,474 @@ fn display_transform_matrix(
             });
     });
 }
+
+fn display_scale(
+    ui: &mut egui::Ui,
+    ent: Entity,
+    arrow: &mut ArrowIO,
+    events: &mut EventWriter<ApplyTransformCommand>,
+) {
+    let mut changed = false;
+    ui.collapsing("Scale", |ui| {
+        changed |= display_vec(ui, "XYZ", &mut arrow.scale);
+
+        ui.horizontal(|ui| {
+            if ui.button("Uniform").on_hover_text("Use the X scale for all axes").clicked() {
+                arrow.scale = Vec3::splat(arrow.scale.x);
+                changed = true;
+            }
+            if ui.button("Reset").clicked() {
+                arrow.scale = Vec3::ONE;
+                changed = true;
+            }
+        });
+
+        if arrow.scale.cmpeq(Vec3::ZERO).any() {
+            ui.colored_label(ui.visuals().warn_fg_color, "Zero scale, the arrow is flattened");
+        } else if arrow.scale.x * arrow.scale.y * arrow.scale.z < 0.0 {
+            ui.colored_label(ui.visuals().warn_fg_color, "Negative scale, the arrow is mirrored");
+        }
+    });
+
+    if changed {
+        events.write(ApplyTransformCommand::scale(ent, arrow.scale));
+    }
+}
+
+#[derive(Clone, Copy)]
+enum EditedValue {
+    Position,
+    Quaternion,
+    Euler,
+}
+
+/// Values in the main coordinate system and in the extra ones side by side, any of them can be edited.
+/// `coord` is the coordinate system of the object, that edits are applied in
+fn display_coordinate_systems(
+    ui: &mut egui::Ui,
+    ent: Entity,
+    coord: &CoordinateSystem,
+    main_coord: &CoordinateSystem,
+    extra_coords: &ExtraCoordinateSystems,
+    tf: &Transform,
+    events: &mut EventWriter<ApplyTransformCommand>,
+) {
+    let extra: Vec<_> = extra_coords
+        .0
+        .iter()
+        .map(|coords| (coords.name.as_str(), coords.coordinate_system()))
+        .collect();
+    let systems: Vec<_> = std::iter::once(("Main", main_coord))
+        .chain(extra.iter().map(|(name, coords)| (*name, coords)))
+        .collect();
+
+    let mut values: Vec<_> = systems
+        .iter()
+        .map(|(_, coords)| {
+            let quat = geometry::convert_rotation(&coords.internal2user, tf.rotation);
+            let pos = geometry::convert_position_i2u(
+                &coords.internal2user,
+                coords.positions_scale,
+                coords.position_mode,
+                tf.rotation,
+                tf.translation,
+            );
+            let (x, y, z) = quat.to_euler(EulerRot::XYZ);
+            (pos, quat, Vec3::new(x, y, z).map(f32::to_degrees))
+        })
+        .collect();
+
+    let mut edited = None;
+    ui.collapsing("Coordinate Systems", |ui| {
+        if extra_coords.0.is_empty() {
+            ui.label("Other coordinate systems can be added in the settings");
+        }
+
+        egui::Grid::new(ent.index().to_string() + "coords")
+            .num_columns(systems.len() + 1)
+            .show(ui, |ui| {
+                ui.label("");
+                for (name, _) in &systems {
+                    ui.label(*name);
+                }
+                ui.end_row();
+
+                for (i, label) in ["Pos X", "Pos Y", "Pos Z"].into_iter().enumerate() {
+                    ui.label(label);
+                    for (column, (pos, ..)) in values.iter_mut().enumerate() {
+                        let widget = egui::DragValue::new(&mut pos[i]).speed(SCROLL_SPEED_POS);
+                        if ui.add(widget).changed() {
+                            edited = Some((column, EditedValue::Position));
+                        }
+                    }
+                    ui.end_row();
+                }
+
+                for (i, label) in ["Quat W", "Quat X", "Quat Y", "Quat Z"].into_iter().enumerate() {
+                    ui.label(label);
+                    for (column, (_, quat, _)) in values.iter_mut().enumerate() {
+                        let value = match i {
+                            0 => &mut quat.w,
+                            1 => &mut quat.x,
+                            2 => &mut quat.y,
+                            _ => &mut quat.z,
+                        };
+                        let widget = egui::DragValue::new(value).speed(SCROLL_SPEED_POS).max_decimals(4);
+                        if ui.add(widget).changed() {
+                            edited = Some((column, EditedValue::Quaternion));
+                        }
+                    }
+                    ui.end_row();
+                }
+
+                for (i, label) in ["Euler X", "Euler Y", "Euler Z"].into_iter().enumerate() {
+                    ui.label(label);
+                    for (column, (.., euler)) in values.iter_mut().enumerate() {
+                        let widget = egui::DragValue::new(&mut euler[i]).speed(SCROLL_SPEED_DEG);
+                        if ui.add(widget).changed() {
+                            edited = Some((column, EditedValue::Euler));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+
+    let Some((column, value)) = edited else {
+        return;
+    };
+
+    // edited values are converted to internal coordinates, and from there to the coordinate system of the object
+    let (_, coords) = systems[column];
+    let (pos, quat, euler) = values[column];
+    let quat = match value {
+        EditedValue::Euler => {
+            let Vec3 { x, y, z } = euler.map(f32::to_radians);
+            Quat::from_euler(EulerRot::XYZ, x, y, z)
+        }
+        _ => Vec4::from(quat).try_normalize().map_or(Quat::IDENTITY, Quat::from_vec4),
+    };
+
+    let rotation = geometry::convert_rotation(&coords.user2internal, quat);
+    let translation = geometry::convert_position_u2i(
+        &coords.user2internal,
+        coords.positions_scale,
+        coords.position_mode,
+        rotation,
+        pos,
+    );
+
+    events.write(ApplyTransformCommand::rot_quat(
+        ent,
+        geometry::convert_rotation(&coord.internal2user, rotation),
+    ));
+    events.write(ApplyTransformCommand::pos(
+        ent,
+        geometry::convert_position_i2u(
+            &coord.internal2user,
+            coord.positions_scale,
+            coord.position_mode,
+            rotation,
+            translation,
+        ),
+    ));
+}
+
+/// Editing a part rewrites the matrix fields
+fn display_decomposition(ui: &mut egui::Ui, mat: Mat4, arrow: &mut ArrowIO) {
+    let Some(mut parts) = TransformDecomposition::new(mat) else {
+        ui.colored_label(ui.visuals().error_fg_color, "Degenerate matrix, an axis is scaled to zero");
+        return;
+    };
+
+    let mut changed = false;
+    ui.collapsing("Decomposition", |ui| {
+        changed |= display_vec(ui, "Translation", &mut parts.translation);
+
+        let (x, y, z) = parts.rotation.to_euler(EulerRot::XYZ);
+        let mut euler = Vec3::new(x, y, z).map(f32::to_degrees);
+        let mut euler_changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Rotation (XYZ)");
+            for value in euler.as_mut() {
+                let widget = egui::DragValue::new(value).speed(SCROLL_SPEED_DEG);
+                euler_changed |= ui.add(widget).changed();
+            }
+        });
+        if euler_changed {
+            let Vec3 { x, y, z } = euler.map(f32::to_radians);
+            parts.rotation = Quat::from_euler(EulerRot::XYZ, x, y, z);
+            changed = true;
+        }
+
+        changed |= display_vec(ui, "Scale", &mut parts.scale);
+        changed |= display_vec(ui, "Shear (XY, XZ, YZ)", &mut parts.shear);
+
+        ui.horizontal(|ui| {
+            ui.label("Projective row");
+            for value in parts.projective.as_mut() {
+                let widget = egui::DragValue::new(value).speed(SCROLL_SPEED_POS);
+                changed |= ui.add(widget).changed();
+            }
+        });
+    });
+
+    if parts.scale.x < 0.0 {
+        ui.colored_label(ui.visuals().warn_fg_color, "Negative scale, the matrix flips handedness");
+    }
+    if parts.has_shear() || parts.is_projective() {
+        let text = match (parts.has_shear(), parts.is_projective()) {
+            (true, true) => "Shear and projection",
+            (true, false) => "Shear",
+            _ => "Projection",
+        };
+        ui.horizontal(|ui| {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!("Not a pure TRS matrix. {text} will be dropped when applied"),
+            );
+            if ui.button("Remove").clicked() {
+                parts.shear = Vec3::ZERO;
+                parts.projective = Vec4::W;
+                changed = true;
+            }
+        });
+    }
+
+    if changed {
+        arrow.tf_mat = conv::mat4_to_strings(&parts.to_matrix(), conv::MatStrMode::RowMajor);
+    }
+}
+
+fn display_vec(ui: &mut egui::Ui, name: &'static str, vec: &mut Vec3) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label(name);
+        for value in vec.as_mut() {
+            let widget = egui::DragValue::new(value).speed(SCROLL_SPEED_POS);
+            changed |= ui.add(widget).changed();
+        }
+    });
+    changed
+}
+
+fn display_look(
+    ui: &mut egui::Ui,
+    ent: Entity,
+    arrow: &mut ArrowIO,
+    targets: &[(Entity, String)],
+    events: &mut EventWriter<ApplyTransformCommand>,
+) {
+    let other_arrows = || targets.iter().filter(|(target, _)| *target != ent);
+
+    // changes are applied immediately
+    let mut changed = false;
+    ui.collapsing("Look at", |ui| {
+        ui.horizontal(|ui| {
+            let modes = [
+                ("Direction", Some(LookTarget::Direction(Vec3::X))),
+                ("Point", Some(LookTarget::Point(Vec3::ZERO))),
+                ("Arrow", other_arrows().next().map(|(target, _)| LookTarget::Arrow(*target))),
+            ];
+            for (index, (name, default_target)) in modes.into_iter().enumerate() {
+                let selected = index
+                    == match arrow.look {
+                        LookTarget::Direction(_) => 0,
+                        LookTarget::Point(_) => 1,
+                        LookTarget::Arrow(_) => 2,
+                    };
+                let response = ui.add_enabled(default_target.is_some(), egui::Button::selectable(selected, name));
+                if response.clicked() && !selected {
+                    arrow.look = default_target.unwrap();
+                }
+            }
+        });
+
+        match &mut arrow.look {
+            LookTarget::Direction(direction) => changed |= display_vec(ui, "Direction: ", direction),
+            LookTarget::Point(point) => changed |= display_vec(ui, "Point: ", point),
+            LookTarget::Arrow(target) => {
+                let target_name = targets
+                    .iter()
+                    .find(|(other, _)| other == target)
+                    .map_or("", |(_, name)| name.as_str());
+                egui::ComboBox::from_id_salt(ent.index().to_string() + "look_arrow")
+                    .selected_text(target_name)
+                    .show_ui(ui, |ui| {
+                        for (other, name) in other_arrows() {
+                            changed |= ui.selectable_value(target, *other, name).changed();
+                        }
+                    });
+            }
+        }
+        changed |= display_vec(ui, "Up hint: ", &mut arrow.up);
+
+        match arrow.look_fallback {
+            None => {}
+            Some(geometry::LookUpFallback::CurrentUp) => {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "Target is parallel to the up hint, the current up direction was used instead",
+                );
+            }
+            Some(geometry::LookUpFallback::Arbitrary) => {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "Target is parallel to the up hint and to the current up direction, an arbitrary perpendicular up was used",
+                );
+            }
+        }
+
+        if ui.button("Apply").clicked() {
+            changed = true;
+        }
+    });
+
+    if changed {
+        events.write(ApplyTransformCommand::look_at(ent, arrow.look, arrow.up));
+    }
+}
+
+fn display_from_to(
+    ui: &mut egui::Ui,
+    ent: Entity,
+    coord: &CoordinateSystem,
+    arrow: &mut ArrowIO,
+    events: &mut EventWriter<ApplyTransformCommand>,
+) {
+    ui.collapsing("From/to vectors", |ui| {
+        display_vec(ui, "From: ", &mut arrow.arc_from);
+        display_vec(ui, "To: ", &mut arrow.arc_to);
+
+        let rotation = rotation::rotation_arc(coord, arrow.arc_from, arrow.arc_to);
+        match rotation {
+            None => {
+                ui.colored_label(ui.visuals().error_fg_color, "Vectors can't be zero");
+            }
+            Some(_) if arrow.arc_from.normalize().dot(arrow.arc_to.normalize()) < -0.9999 => {
+                ui.colored_label(ui.visuals().warn_fg_color, "Vectors are opposite, the rotation axis is arbitrary");
+            }
+            Some(_) => {}
+        }
+
+        if ui.add_enabled(rotation.is_some(), egui::Button::new("Apply")).clicked() {
+            events.write(ApplyTransformCommand::rot_quat(ent, rotation.unwrap()));
+        }
+    });
+}
+
+fn display_basis(
+    ui: &mut egui::Ui,
+    ent: Entity,
+    coord: &CoordinateSystem,
+    arrow: &mut ArrowIO,
+    events: &mut EventWriter<ApplyTransformCommand>,
+) {
+    ui.collapsing("Basis vectors", |ui| {
+        display_vec(ui, "Side: ", &mut arrow.basis[0]);
+        display_vec(ui, "Up: ", &mut arrow.basis[1]);
+        display_vec(ui, "Forward: ", &mut arrow.basis[2]);
+
+        if ui.button("From current rotation").clicked() {
+            let current = conv::strings_to_quat(&arrow.quat, conv::QuatStrMode::WXYZ).normalize();
+            arrow.basis = rotation::basis_of(coord, current);
+        }
+
+        let result = rotation::rotation_from_basis(coord, arrow.basis);
+        match &result {
+            None => {
+                ui.colored_label(ui.visuals().error_fg_color, "Forward and up must be non-zero and not parallel");
+            }
+            Some(result) if result.reflected => {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "Vectors have the opposite handedness, side will be flipped",
+                );
+            }
+            Some(result) if result.adjusted => {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "Vectors are not orthonormal, they will be orthonormalized keeping forward",
+                );
+            }
+            Some(_) => {}
+        }
+
+        if ui.add_enabled(result.is_some(), egui::Button::new("Apply")).clicked() {
+            events.write(ApplyTransformCommand::rot_quat(ent, result.unwrap().rotation));
+        }
+    });
+}
+
+fn display_probe(
+    cmd: &mut Commands,
+    ui: &mut egui::Ui,
+    clip: &mut EguiClipboard,
+    ent: Entity,
+    coord: &CoordinateSystem,
+    tf: &Transform,
+    probe: Option<Mut<Probe>>,
+) {
+    ui.collapsing("Probe vector", |ui| {
+        let Some(mut probe) = probe else {
+            if ui.button("Add").clicked() {
+                cmd.entity(ent).insert(Probe::default());
+            }
+            return;
+        };
+
+        // changes are recorded in the history
+        let mut changed = display_vec(ui, "Vector: ", &mut probe.bypass_change_detection().vector);
+        changed |= ui.checkbox(&mut probe.bypass_change_detection().translate, "Apply translation").changed();
+        if changed {
+            probe.set_changed();
+        }
+
+        let result = probe.apply(coord, tf);
+        let strings = conv::vec_to_strings(result.user);
+        ui.horizontal(|ui| {
+            ui.label(format!("Result: {}", strings.join(", ")));
+            if ui.button("Copy").clicked() {
+                clip_copy(clip, &strings);
+            }
+        });
+
+        if ui.button("Remove").clicked() {
+            cmd.entity(ent).remove::<Probe>();
+        }
+    });
+}
+
+fn display_swing_twist(ui: &mut egui::Ui, clip: &mut EguiClipboard, rotation: Quat, arrow: &mut ArrowIO) {
+    ui.collapsing("Swing-twist", |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Axis: ");
+            for axis in Axis::all() {
+                if ui.selectable_label(arrow.twist_axis == axis.to_vec(), axis.name()).clicked() {
+                    arrow.twist_axis = axis.to_vec();
+                }
+            }
+        });
+        display_vec(ui, "", &mut arrow.twist_axis);
+
+        let Some(decomposition) = rotation::swing_twist(rotation, arrow.twist_axis) else {
+            ui.colored_label(ui.visuals().error_fg_color, "Axis can't be zero");
+            return;
+        };
+
+        let display_part = |ui: &mut egui::Ui, clip: &mut EguiClipboard, name: &str, quat: Quat, angle: f32| {
+            let strings = conv::quat_to_strings(quat, conv::QuatStrMode::WXYZ);
+            ui.horizontal(|ui| {
+                ui.label(format!("{name}: {:.2}°", angle.to_degrees()));
+                if ui.button("Copy").clicked() {
+                    clip_copy(clip, &strings);
+                }
+            });
+            ui.label(format!("W {}, X {}, Y {}, Z {}", strings[0], strings[1], strings[2], strings[3]));
+        };
+        display_part(ui, clip, "Swing", decomposition.swing, decomposition.swing_angle);
+        display_part(ui, clip, "Twist", decomposition.twist, decomposition.twist_angle);
+
+        ui.checkbox(&mut arrow.show_twist_arc, "Show twist arc");
+    });
+}
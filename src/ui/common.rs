@@ -14,3 +14,11 @@ pub fn clip_paste(clip: &mut EguiClipboard, data: &mut [String]) {
         .zip(data)
         .for_each(|(value, data)| *data = value);
 }
+
+/// Only marks the name as changed when it is edited, history is recorded on changes
+pub fn name_edit_ui(ui: &mut egui::Ui, name: &mut Mut<Name>) {
+    let mut text = name.to_string();
+    if ui.add(egui::TextEdit::singleline(&mut text).desired_width(100.0)).changed() {
+        name.set(text);
+    }
+}
@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+use super::{*, common::*};
+
+pub fn group_ui(
+    cmd: &mut Commands,
+    ui: &mut egui::Ui,
+    InspectorContext { models, extra_coords, .. }: &InspectorContext,
+    GroupsQueryItem { ent, mut name, mut repr, computed }: GroupsQueryItem,
+    InspectorEvents { objects: object_events, .. }: &mut InspectorEvents,
+) {
+    let locked = computed.locked;
+
+    ui.horizontal(|ui| {
+        if locked {
+            ui.disable();
+        }
+        if ui.button("Add Arrow").clicked() {
+            cmd.spawn((crate::objects::Arrow, InGroup(ent)));
+        }
+        if ui.button("Add Group").clicked() {
+            cmd.spawn((crate::objects::Group, InGroup(ent)));
+        }
+    });
+
+    ui.horizontal(|ui| {
+        if ui.button("Duplicate").clicked() {
+            object_events.write(ObjectCommand::Duplicate(ent));
+        }
+        if ui.button("Copy").clicked() {
+            object_events.write(ObjectCommand::Copy(ent));
+        }
+        if ui.add_enabled(!locked, egui::Button::new("Paste")).clicked() {
+            object_events.write(ObjectCommand::Paste(ent));
+        }
+        if ui.add_enabled(!locked, egui::Button::new("Delete")).clicked() {
+            cmd.entity(ent).despawn();
+        }
+    });
+
+    egui::CollapsingHeader::new("Settings")
+        .id_salt("group_settings")
+        .default_open(true)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Name: ");
+                ui.add_enabled_ui(!locked, |ui| {
+                    name_edit_ui(ui, &mut name);
+                });
+            });
+
+            if repr_settings::repr_settings_ui(false, ui, models, repr.bypass_change_detection(), computed) {
+                repr.set_changed();
+            }
+            ui.add_enabled_ui(!locked, |ui| {
+                if repr_settings::coordinates_settings_ui(ui, extra_coords, repr.bypass_change_detection(), computed) {
+                    repr.set_changed();
+                }
+            });
+        });
+}
@@ -1,17 +1,26 @@
-use bevy::{ecs::query::QueryData, prelude::*};
-use bevy_egui::{EguiClipboard, EguiContexts, EguiPlugin, EguiPrimaryContextPass, egui};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bevy::{ecs::{query::QueryData, system::SystemParam}, prelude::*};
+use bevy_egui::{EguiClipboard, EguiContexts, EguiGlobalSettings, EguiPlugin, EguiPrimaryContextPass, egui};
 
 use crate::{
     repr,
+    model::{Model, ModelLibrary},
+    export::{ExportGltfCommand, ExportStatus},
+    history::{History, HistoryCommand},
+    scene::ObjectCommand,
+    selection::Selection,
     conversion as conv,
     group::{GroupedObjects, InGroup, InGroupDisplaySettings},
-    geometry::{ApplyTransformCommand, Axis, Hand, PositionMode},
+    geometry::{ApplyTransformCommand, Axis, CoordinateSystem, CoordinateSystemsParam, Hand, PositionMode},
 };
 
 mod common;
 mod arrow;
 mod settings;
 mod repr_settings;
+mod outliner;
+mod group;
 
 #[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct UiSet;
@@ -21,10 +30,16 @@ struct UiPlugin;
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.world_mut().spawn(ConfigIO::default());
+        app.init_resource::<PanelLayout>();
+        // ui is attached to the x-ray camera, which is spawned in setup
+        app.insert_resource(EguiGlobalSettings {
+            auto_create_primary_context: false,
+            ..default()
+        });
 
         app.add_systems(
             EguiPrimaryContextPass,
-            (settings::system_settings_ui, system_arrows_ui).chain().in_set(UiSet),
+            (settings::system_settings_ui, outliner::system_outliner_ui, system_inspector_ui).chain().in_set(UiSet),
         );
     }
 }
@@ -39,17 +54,34 @@ impl PluginGroup for UiPlugins {
     }
 }
 
-#[derive(Component)]
-#[require(repr::ReprSettings)]
+/// Outliner and inspector are either docked to the side of the window or floating
+#[derive(Resource, PartialEq)]
+pub struct PanelLayout {
+    pub docked: bool,
+}
+
+impl Default for PanelLayout {
+    fn default() -> Self {
+        Self { docked: true }
+    }
+}
+
+#[derive(Component, Clone, PartialEq)]
+#[require(repr::ReprSettings, ExtraCoordinateSystems)]
 pub struct ConfigIO {
     pub up: Axis,
     pub forward: Axis,
     pub up_sign: f32,
     pub forward_sign: f32,
     pub hand: Hand,
+    /// columns are the user X, Y and Z axes in the coordinates of the preset above,
+    /// only used while it is orthonormal
+    pub custom_basis: Option<Mat3>,
     /// if true, changing coordinate system will preserve numeric values of the quaternion
     /// instead of its direction in the internal coordinate system
     pub keep_numbers: bool,
+    pub quat_sign: crate::rotation::QuatSign,
+    pub euler_range: crate::rotation::AngleRange,
 
     pub position_mode: PositionMode,
     pub positions_scale: f32,
@@ -63,13 +95,81 @@ impl Default for ConfigIO {
             forward: Axis::Z,
             forward_sign: -1.0,
             hand: Hand::Right,
+            custom_basis: None,
             keep_numbers: false,
+            quat_sign: default(),
+            euler_range: default(),
             position_mode: default(),
             positions_scale: 1.0,
         }
     }
 }
 
+impl ConfigIO {
+    pub fn valid_custom_basis(&self) -> Option<Mat3> {
+        self.custom_basis
+            .filter(|basis| crate::rotation::check_matrix(*basis).is_orthonormal())
+    }
+}
+
+/// Conventions that values of arrows are displayed in next to the main coordinate system
+#[derive(Component, Clone, PartialEq, Default)]
+pub struct ExtraCoordinateSystems(pub Vec<NamedCoordinates>);
+
+impl ExtraCoordinateSystems {
+    pub fn get(&self, id: repr::CoordinatesId) -> Option<&NamedCoordinates> {
+        self.0.iter().find(|coords| coords.id == id)
+    }
+
+    pub fn find_name(&self, name: &str) -> Option<&NamedCoordinates> {
+        self.0.iter().find(|coords| coords.name == name)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct NamedCoordinates {
+    pub id: repr::CoordinatesId,
+    /// unique among the extra coordinate systems
+    pub name: String,
+    pub up: Axis,
+    pub up_sign: f32,
+    pub forward: Axis,
+    pub forward_sign: f32,
+    pub hand: Hand,
+    pub position_mode: PositionMode,
+    pub positions_scale: f32,
+}
+
+impl NamedCoordinates {
+    pub fn new(name: String) -> Self {
+        // ids are never reused, groups of a deleted system shouldn't switch to a newly added one
+        static CREATED: AtomicU64 = AtomicU64::new(0);
+
+        Self {
+            id: repr::CoordinatesId(CREATED.fetch_add(1, Ordering::Relaxed) + 1),
+            name,
+            up: Axis::Z,
+            up_sign: 1.0,
+            forward: Axis::X,
+            forward_sign: 1.0,
+            hand: Hand::Right,
+            position_mode: default(),
+            positions_scale: 1.0,
+        }
+    }
+
+    pub fn coordinate_system(&self) -> CoordinateSystem {
+        CoordinateSystem::new(
+            self.up.to_vec() * self.up_sign,
+            self.forward.to_vec() * self.forward_sign,
+            self.hand,
+            None,
+            self.position_mode,
+            self.positions_scale,
+        )
+    }
+}
+
 const SCROLL_SPEED_POS: f32 = 0.01;
 const SCROLL_SPEED_DEG: f32 = 0.1;
 const SCROLL_SPEED_SCALE: f32 = 0.01;
@@ -85,121 +185,131 @@ struct ArrowsQuery<'a> {
     computed: &'a repr::ComputedRepresentation,
     in_group: Option<&'a InGroup>,
     in_group_display: Option<&'a mut InGroupDisplaySettings>,
+    user_tf: &'a crate::geometry::UserTransform,
+    tf: &'a Transform,
+    coords: &'a crate::geometry::ObjectCoordinates,
+    probe: Option<&'a mut crate::probe::Probe>,
+}
+
+#[derive(QueryData)]
+#[query_data(mutable)]
+struct GroupsQuery<'a> {
+    ent: Entity,
+    name: &'a mut Name,
+    repr: &'a mut repr::ReprSettings,
+    computed: &'a repr::ComputedRepresentation,
+}
+
+/// What the arrow and group inspectors need to know about the rest of the scene
+struct InspectorContext<'a> {
+    models: &'a ModelLibrary,
+    main_coord: &'a CoordinateSystem,
+    extra_coords: &'a ExtraCoordinateSystems,
+    /// arrows that others can look at
+    targets: Vec<(Entity, String)>,
+}
+
+#[derive(SystemParam)]
+struct InspectorResources<'w> {
+    clip: ResMut<'w, EguiClipboard>,
+    layout: Res<'w, PanelLayout>,
+    selection: Res<'w, Selection>,
+    models: Res<'w, ModelLibrary>,
+}
+
+#[derive(SystemParam)]
+struct InspectorEvents<'w> {
+    transforms: EventWriter<'w, ApplyTransformCommand>,
+    objects: EventWriter<'w, ObjectCommand>,
 }
 
 
-fn system_arrows_ui(
+fn system_inspector_ui(
     mut cmd: Commands,
     mut ctx: EguiContexts,
-    mut clip: ResMut<EguiClipboard>,
-    config_q: Query<Entity, With<ConfigIO>>,
-    mut groups_q: Query<
-        (
-            Entity,
-            &mut Name,
-            &mut crate::group::GroupIO,
-            Option<&GroupedObjects>,
-            &mut repr::ReprSettings,
-            &repr::ComputedRepresentation,
-        ),
-        With<crate::objects::Group>
-    >,
-
+    res: InspectorResources,
+    mut groups_q: Query<GroupsQuery, With<crate::objects::Group>>,
     mut arrows_q: Query<ArrowsQuery, Without<crate::objects::Group>>,
-
-    mut tf_events: EventWriter<ApplyTransformCommand>,
+    systems: CoordinateSystemsParam,
+    mut events: InspectorEvents,
 ) {
+    let InspectorResources { mut clip, layout, selection, models } = res;
     let ctx = ctx.ctx_mut().unwrap();
-    let config_ent = config_q.single().unwrap();
+    let systems = systems.get();
+    let context = InspectorContext {
+        models: &models,
+        main_coord: systems.main,
+        extra_coords: systems.extra,
+        targets: arrows_q
+            .iter()
+            .map(|arrow| (arrow.ent, arrow.name.to_string()))
+            .collect(),
+    };
+
+    // bring the window of the newly selected arrow to front, if it has one
+    if selection.is_changed()
+        && let Some(selected) = selection.selected
+    {
+        ctx.move_to_top(egui::LayerId::new(
+            egui::Order::Middle,
+            egui::Id::new(selected.index()),
+        ));
+    }
 
     for arrow in arrows_q.iter_mut() {
-        if arrow.in_group.is_none() || arrow.in_group.unwrap().0 == config_ent {
+        if arrow.in_group_display.as_ref().is_some_and(|igd| igd.popped_out) {
             egui::Window::new(arrow.name.as_str())
                 .id(egui::Id::new(arrow.ent.index()))
                 .show(ctx, |ui| {
-                    arrow::arrow_ui(&mut cmd, ui, &mut clip, arrow, &mut tf_events);
+                    arrow::arrow_ui(&mut cmd, ui, &mut clip, &context, arrow, &mut events);
                 });
         }
     }
 
+    dockable_panel(ctx, layout.docked, "inspector", "Inspector", |ui| {
+        let Some(selected) = selection.selected else {
+            ui.label("Select an object in the outliner or in the scene");
+            return;
+        };
 
-    for (ent, mut name, mut group, grouped, mut repr, computed) in groups_q.iter_mut() {
-        if let Some(selected) = group.selected_object {
-            if !arrows_q.contains(selected) {
-                group.selected_object = None;
-            }
-        }
-
-        for arrow_ent in grouped.into_iter().flatten() {
-            let arrow = arrows_q.get_mut(arrow_ent).unwrap();
-            if arrow.in_group_display.as_ref().unwrap().popped_out {
-                egui::Window::new(arrow.name.as_str())
-                    .id(egui::Id::new(arrow.ent.index()))
-                    .show(ctx, |ui| {
-                        arrow::arrow_ui(&mut cmd, ui, &mut clip, arrow, &mut tf_events);
-                    });
+        if let Ok(mut arrow) = arrows_q.get_mut(selected) {
+            ui.label(arrow.name.as_str());
+            if let Some(igd) = &mut arrow.in_group_display
+                && igd.popped_out
+            {
+                ui.label("Displayed in its own window");
+                if ui.button("Return to inspector").clicked() {
+                    igd.popped_out = false;
+                }
+                return;
             }
+            arrow::arrow_ui(&mut cmd, ui, &mut clip, &context, arrow, &mut events);
+        } else if let Ok(group) = groups_q.get_mut(selected) {
+            ui.label(group.name.as_str());
+            group::group_ui(&mut cmd, ui, &context, group, &mut events);
         }
+    });
+}
 
-        egui::Window::new(name.as_str())
-            .id(egui::Id::new(ent.index()))
-            .show(ctx, |ui| {
-                if ui.button("Add Arrow").clicked() {
-                    cmd.spawn((crate::objects::Arrow, InGroup(ent), InGroupDisplaySettings::default()));
-                }
-
-                if ui.button("Delete").clicked() {
-                    cmd.entity(ent).despawn();
-                }
+/// Side panel on the right, or a floating window when panels are undocked
+fn dockable_panel(
+    ctx: &egui::Context,
+    docked: bool,
+    id: &'static str,
+    title: &str,
+    add_contents: impl FnOnce(&mut egui::Ui),
+) {
+    let contents = |ui: &mut egui::Ui| {
+        egui::ScrollArea::vertical().show(ui, add_contents);
+    };
 
-                egui::CollapsingHeader::new("Settings")
-                    .id_salt("group_settings")
-                    .show(ui, |ui| {
-                        ui.horizontal(|ui| {
-                            ui.label("Name: ");
-                            name.mutate(|name| {
-                                ui.add(egui::TextEdit::singleline(name).desired_width(100.0));
-                            });
-                        });
-
-                        if repr_settings::repr_settings_ui(false, ui, repr.bypass_change_detection(), &computed) {
-                            repr.set_changed();
-                        }
-                    });
-
-                egui::CollapsingHeader::new("Arrows")
-                    .default_open(true)
-                    .show(ui, |ui| {
-                        for arrow_ent in grouped.into_iter().flatten() {
-                            let arrow = arrows_q.get(arrow_ent).unwrap();
-                            ui.add_enabled_ui(!arrow.in_group_display.unwrap().popped_out, |ui| {
-                                let mut label = arrow.name.to_string();
-                                if arrow.in_group_display.unwrap().popped_out {
-                                    label += " ->";
-                                }
-
-                                let this_selected = group.selected_object == Some(arrow_ent);
-                                let response = ui.selectable_label(this_selected, label);
-                                if response.clicked() {
-                                    group.selected_object = Some(arrow_ent);
-                                }
-                                if this_selected && response.clicked_by(egui::PointerButton::Secondary) {
-                                    group.selected_object = None;
-                                }
-                            });
-                        }
-                    });
-
-                if let Some(selected) = group.selected_object {
-                    let arrow = arrows_q.get_mut(selected).unwrap();
-                    if !arrow.in_group_display.as_ref().unwrap().popped_out {
-                        egui::CollapsingHeader::new("Selected Arrow")
-                            .default_open(true)
-                            .show(ui, |ui| {
-                                arrow::arrow_ui(&mut cmd, ui, &mut clip, arrow, &mut tf_events);
-                            });
-                    }
-                }
-            });
+    if docked {
+        egui::SidePanel::right(id).resizable(true).show(ctx, |ui| {
+            ui.heading(title);
+            ui.separator();
+            contents(ui);
+        });
+    } else {
+        egui::Window::new(title).id(egui::Id::new(id)).show(ctx, contents);
     }
 }
@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, egui};
+
+use super::*;
+
+type ObjectsQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Name,
+        Has<crate::objects::Group>,
+        Option<&'static GroupedObjects>,
+        &'static mut repr::ReprSettings,
+        &'static repr::ComputedRepresentation,
+    ),
+    Or<(With<crate::objects::Arrow>, With<crate::objects::Group>)>,
+>;
+
+#[derive(Default)]
+pub struct OutlinerState {
+    filter: String,
+    renaming: Option<Entity>,
+}
+
+/// Tree of all objects, they can be dragged into other groups
+pub fn system_outliner_ui(
+    mut ctx: EguiContexts,
+    mut layout: ResMut<PanelLayout>,
+    mut selection: ResMut<Selection>,
+    mut state: Local<OutlinerState>,
+    config_q: Query<(Entity, Option<&GroupedObjects>), With<ConfigIO>>,
+    mut objects_q: ObjectsQuery,
+    mut object_events: EventWriter<ObjectCommand>,
+) {
+    let ctx = ctx.ctx_mut().unwrap();
+    let (config_ent, top_level) = config_q.single().unwrap();
+    let top_level: Vec<_> = top_level.into_iter().flatten().collect();
+
+    let mut outliner = Outliner {
+        objects_q: &mut objects_q,
+        state: &mut state,
+        selected: selection.selected,
+        object_events: &mut object_events,
+    };
+
+    let mut docked = layout.docked;
+    dockable_panel(ctx, layout.docked, "outliner", "Outliner", |ui| {
+        let text = if docked { "Undock panels" } else { "Dock panels" };
+        if ui.button(text).clicked() {
+            docked = !docked;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Search: ");
+            ui.add(egui::TextEdit::singleline(&mut outliner.state.filter).desired_width(120.0));
+        });
+        ui.separator();
+
+        // dropping on the scene moves objects to the top level
+        let response = ui.label("Scene");
+        outliner.drop_target_ui(ui, &response, config_ent);
+
+        ui.indent("outliner", |ui| {
+            for ent in top_level {
+                outliner.object_tree_ui(ui, ent, config_ent);
+            }
+        });
+    });
+
+    // selection is only written when it changes, so that its change detection stays meaningful
+    if outliner.selected != selection.selected {
+        selection.selected = outliner.selected;
+    }
+    layout.set_if_neq(PanelLayout { docked });
+}
+
+struct Outliner<'a, 'w, 's, 'e> {
+    objects_q: &'a mut ObjectsQuery<'w, 's>,
+    state: &'a mut OutlinerState,
+    selected: Option<Entity>,
+    object_events: &'a mut EventWriter<'e, ObjectCommand>,
+}
+
+impl Outliner<'_, '_, '_, '_> {
+    /// Objects are shown if their name or the name of anything inside of them matches the search
+    fn is_shown(&self, ent: Entity) -> bool {
+        let Ok((name, _, grouped, ..)) = self.objects_q.get(ent) else {
+            return false;
+        };
+
+        self.state.filter.is_empty()
+            || name.to_lowercase().contains(&self.state.filter.to_lowercase())
+            || grouped.into_iter().flatten().any(|child| self.is_shown(child))
+    }
+
+    fn object_tree_ui(&mut self, ui: &mut egui::Ui, ent: Entity, group: Entity) {
+        if !self.is_shown(ent) {
+            return;
+        }
+        let Ok((_, is_group, grouped, ..)) = self.objects_q.get(ent) else {
+            return;
+        };
+
+        // dropping on a group moves objects into it, dropping on an arrow moves them next to it
+        if is_group {
+            let children: Vec<_> = grouped.into_iter().flatten().collect();
+            let id = ui.make_persistent_id(("outliner", ent));
+            let mut header = egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true);
+            if !self.state.filter.is_empty() {
+                header.set_open(true);
+            }
+
+            header
+                .show_header(ui, |ui| {
+                    self.object_row_ui(ui, ent, ent);
+                })
+                .body(|ui| {
+                    for child in children {
+                        self.object_tree_ui(ui, child, ent);
+                    }
+                });
+        } else {
+            ui.horizontal(|ui| {
+                self.object_row_ui(ui, ent, group);
+            });
+        }
+    }
+
+    fn object_row_ui(&mut self, ui: &mut egui::Ui, ent: Entity, drop_group: Entity) {
+        let Ok((mut name, _, _, mut repr, computed)) = self.objects_q.get_mut(ent) else {
+            return;
+        };
+
+        let mut visible = computed.visible;
+        if ui.checkbox(&mut visible, "").on_hover_text("Visible").changed() {
+            repr.visible = Some(visible);
+        }
+        let mut solo = computed.solo;
+        if ui.toggle_value(&mut solo, "S").on_hover_text("Solo").changed() {
+            repr.solo = Some(solo);
+        }
+        let mut locked = computed.locked;
+        if ui.toggle_value(&mut locked, "L").on_hover_text("Locked").changed() {
+            repr.locked = Some(locked);
+        }
+
+        if self.state.renaming == Some(ent) && !computed.locked {
+            // edited as a copy, so that the name is only marked as changed when it is edited
+            let mut text = name.to_string();
+            let response = ui.add(egui::TextEdit::singleline(&mut text).desired_width(120.0));
+            if response.changed() {
+                name.set(text);
+            }
+            if response.lost_focus() {
+                self.state.renaming = None;
+            } else if !response.has_focus() {
+                response.request_focus();
+            }
+            return;
+        }
+
+        let selected = self.selected == Some(ent);
+        let response = ui.dnd_drag_source(egui::Id::new(("outliner_drag", ent)), ent, |ui| {
+            ui.selectable_label(selected, name.as_str())
+        });
+
+        let label = response.inner;
+        if label.double_clicked() {
+            self.state.renaming = Some(ent);
+        } else if label.clicked() {
+            self.selected = Some(ent);
+        }
+
+        self.drop_target_ui(ui, &response.response, drop_group);
+    }
+
+    fn drop_target_ui(&mut self, ui: &mut egui::Ui, response: &egui::Response, group: Entity) {
+        if response.dnd_hover_payload::<Entity>().is_some() {
+            ui.painter().rect_stroke(
+                response.rect,
+                2.0,
+                ui.visuals().selection.stroke,
+                egui::StrokeKind::Outside,
+            );
+        }
+
+        if let Some(ent) = response.dnd_release_payload::<Entity>() {
+            self.object_events.write(ObjectCommand::Move { ent: *ent, group });
+        }
+    }
+}
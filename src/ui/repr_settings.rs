@@ -2,16 +2,45 @@ use bevy::prelude::*;
 use bevy_egui::egui;
 
 use super::*;
-use crate::repr;
+use crate::{model::ModelLibrary, repr};
 
 pub fn repr_settings_ui(
     is_always_on: bool,
     ui: &mut egui::Ui,
+    models: &ModelLibrary,
     repr: &mut repr::ReprSettings,
     computed: &repr::ComputedRepresentation,
 ) -> bool {
     let mut changed = false;
 
+    ui.horizontal(|ui| {
+        changed |= overridable_field(
+            is_always_on,
+            ui,
+            "Shape: ",
+            &mut repr.shape,
+            &computed.shape,
+            |ui, shape| {
+                let shape_name = |shape: &repr::Shape| match shape {
+                    repr::Shape::Model(id) => models
+                        .get(*id)
+                        .map_or(shape.name(), |model| model.name.as_str()),
+                    _ => shape.name(),
+                };
+
+                let mut changed = false;
+                egui::ComboBox::from_id_salt("shape")
+                    .selected_text(shape_name(shape))
+                    .show_ui(ui, |ui| {
+                        let models = models.iter().map(|(id, _)| repr::Shape::Model(id));
+                        for option in repr::Shape::all().into_iter().chain(models) {
+                            changed |= ui.selectable_value(shape, option, shape_name(&option)).changed();
+                        }
+                    });
+                changed
+            },
+        );
+    });
     ui.horizontal(|ui| {
         changed |= overridable_field(
             is_always_on,
@@ -22,7 +51,7 @@ pub fn repr_settings_ui(
             |ui, color| {
                 let mut rgb = color.to_f32_array_no_alpha();
                 if egui::color_picker::color_edit_button_rgb(ui, &mut rgb).changed() {
-                    *color = LinearRgba::from_f32_array_no_alpha(rgb).into();
+                    *color = LinearRgba::from_f32_array_no_alpha(rgb);
                     true
                 } else {
                     false
@@ -60,6 +89,81 @@ pub fn repr_settings_ui(
             },
         );
     });
+    ui.horizontal(|ui| {
+        changed |= overridable_field(
+            is_always_on,
+            ui,
+            "Opacity: ",
+            &mut repr.opacity,
+            &computed.opacity,
+            |ui, opacity| {
+                let widget = egui::DragValue::new(opacity)
+                    .speed(SCROLL_SPEED_SCALE)
+                    .range(0.0..=1.0);
+                ui.add(widget).changed()
+            },
+        );
+    });
+    ui.horizontal(|ui| {
+        changed |= overridable_field(
+            is_always_on,
+            ui,
+            "X-ray: ",
+            &mut repr.xray,
+            &computed.xray,
+            |ui, xray| ui.checkbox(xray, "see through").changed(),
+        );
+    });
+    ui.horizontal(|ui| {
+        changed |= overridable_field(
+            is_always_on,
+            ui,
+            "Triad: ",
+            &mut repr.triad,
+            &computed.triad,
+            |ui, triad| ui.checkbox(triad, "show").changed(),
+        );
+    });
+    ui.horizontal(|ui| {
+        changed |= overridable_field(
+            is_always_on,
+            ui,
+            "Triad labels: ",
+            &mut repr.triad_labels,
+            &computed.triad_labels,
+            |ui, labels| ui.checkbox(labels, "show").changed(),
+        );
+    });
+    ui.horizontal(|ui| {
+        changed |= overridable_field(
+            is_always_on,
+            ui,
+            "Visible: ",
+            &mut repr.visible,
+            &computed.visible,
+            |ui, visible| ui.checkbox(visible, "show").changed(),
+        );
+    });
+    ui.horizontal(|ui| {
+        changed |= overridable_field(
+            is_always_on,
+            ui,
+            "Solo: ",
+            &mut repr.solo,
+            &computed.solo,
+            |ui, solo| ui.checkbox(solo, "only show solo objects").changed(),
+        );
+    });
+    ui.horizontal(|ui| {
+        changed |= overridable_field(
+            is_always_on,
+            ui,
+            "Locked: ",
+            &mut repr.locked,
+            &computed.locked,
+            |ui, locked| ui.checkbox(locked, "no edits").changed(),
+        );
+    });
     // ui.horizontal(|ui| {
     //     changed |= overridable_field(
     //         is_always_on,
@@ -90,6 +194,46 @@ pub fn repr_settings_ui(
     changed
 }
 
+/// Groups can choose the coordinate system that values of everything inside of them are in
+pub fn coordinates_settings_ui(
+    ui: &mut egui::Ui,
+    extra_coords: &ExtraCoordinateSystems,
+    repr: &mut repr::ReprSettings,
+    computed: &repr::ComputedRepresentation,
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        changed |= overridable_field(
+            false,
+            ui,
+            "Coordinates: ",
+            &mut repr.coordinates,
+            &computed.coordinates,
+            |ui, coordinates| {
+                let name = |coordinates: &repr::Coordinates| match coordinates {
+                    repr::Coordinates::Main => "Main".to_string(),
+                    repr::Coordinates::Named(id) => extra_coords
+                        .get(*id)
+                        .map_or_else(|| "(deleted)".to_string(), |coords| coords.name.clone()),
+                };
+
+                let mut changed = false;
+                egui::ComboBox::from_id_salt("coordinates")
+                    .selected_text(name(coordinates))
+                    .show_ui(ui, |ui| {
+                        let named = extra_coords.0.iter().map(|coords| repr::Coordinates::Named(coords.id));
+                        for option in std::iter::once(repr::Coordinates::Main).chain(named) {
+                            let text = name(&option);
+                            changed |= ui.selectable_value(coordinates, option, text).changed();
+                        }
+                    });
+                changed
+            },
+        );
+    });
+    changed
+}
+
 fn overridable_field<T, F>(
     is_always_on: bool,
     ui: &mut egui::Ui,
@@ -1,19 +1,60 @@
 use super::*;
+use crate::rotation::{AngleRange, QuatSign};
+
+#[derive(SystemParam)]
+pub struct SettingsResources<'w> {
+    models: ResMut<'w, ModelLibrary>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    animation: ResMut<'w, crate::animation::CoordinateAnimation>,
+    export_status: Res<'w, ExportStatus>,
+    history: Res<'w, History>,
+}
+
+#[derive(SystemParam)]
+pub struct SettingsEvents<'w> {
+    export: EventWriter<'w, ExportGltfCommand>,
+    history: EventWriter<'w, HistoryCommand>,
+    objects: EventWriter<'w, ObjectCommand>,
+}
+
+/// Text fields and other inputs that aren't stored anywhere else
+#[derive(Default)]
+pub struct SettingsInputs {
+    model_path: String,
+    export_path: Option<String>,
+    basis_euler: Vec3,
+}
 
 pub fn system_settings_ui(
     mut cmd: Commands,
     mut ctx: EguiContexts,
+    res: SettingsResources,
+    mut inputs: Local<SettingsInputs>,
+    events: SettingsEvents,
     mut config_q: Query<(
         Entity,
         &mut ConfigIO,
         &mut repr::ReprSettings,
         &repr::ComputedRepresentation,
+        &mut ExtraCoordinateSystems,
     )>,
 ) {
-    let (config_ent, mut config, mut repr, computed) = config_q.single_mut().unwrap();
+    let SettingsResources { mut models, mut meshes, mut animation, export_status, history } = res;
+    let SettingsEvents { export: mut export_events, history: mut history_events, objects: mut object_events } = events;
+    let SettingsInputs { model_path, export_path, basis_euler } = &mut *inputs;
+    let (config_ent, mut config, mut repr, computed, mut extra_coords) = config_q.single_mut().unwrap();
     let ctx = ctx.ctx_mut().unwrap();
 
     egui::Window::new("Settings").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            if ui.add_enabled(history.can_undo(), egui::Button::new("Undo")).clicked() {
+                history_events.write(HistoryCommand::Undo);
+            }
+            if ui.add_enabled(history.can_redo(), egui::Button::new("Redo")).clicked() {
+                history_events.write(HistoryCommand::Redo);
+            }
+        });
+
         if ui.button("Add Group").clicked() {
             cmd.spawn((crate::objects::Group, InGroup(config_ent)));
         }
@@ -22,13 +63,64 @@ pub fn system_settings_ui(
             cmd.spawn((crate::objects::Arrow, InGroup(config_ent)));
         }
 
+        if ui.button("Paste").clicked() {
+            object_events.write(ObjectCommand::Paste(config_ent));
+        }
+
         ui.collapsing("Default Settings", |ui| {
-            if repr_settings::repr_settings_ui(true, ui, repr.bypass_change_detection(), &computed)
+            if repr_settings::repr_settings_ui(true, ui, &models, repr.bypass_change_detection(), computed)
             {
                 repr.set_changed();
             }
         });
 
+        ui.collapsing("Models", |ui| {
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(model_path).hint_text("path to obj, stl, gltf or glb").desired_width(160.0));
+                if ui.button("Load").clicked() {
+                    models.load_file(&mut meshes, std::path::Path::new(model_path.trim()));
+                }
+            });
+            ui.label("Files can also be dropped onto the window");
+
+            if let Some(error) = &models.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
+            let mut changed = false;
+            for (i, model) in models.bypass_change_detection().models.iter_mut().enumerate() {
+                ui.separator();
+                ui.label(&model.name);
+                changed |= model_conventions_ui(ui, i, model);
+            }
+            if changed {
+                models.set_changed();
+            }
+        });
+
+        ui.collapsing("Export", |ui| {
+            let export_path = export_path.get_or_insert_with(|| "scene.glb".to_string());
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(export_path).desired_width(160.0));
+                if ui.button("Export glTF").clicked() {
+                    export_events.write(ExportGltfCommand { path: export_path.clone() });
+                }
+            });
+
+            ui.label("Text labels, like die faces and axis names, are not exported");
+
+            match &export_status.message {
+                Some(Ok(message)) => {
+                    ui.label(message);
+                }
+                Some(Err(error)) => {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                None => {}
+            }
+        });
+
         egui::CollapsingHeader::new("Coordinate System")
             .default_open(true)
             .show(ui, |ui| {
@@ -128,6 +220,16 @@ pub fn system_settings_ui(
                         ui.end_row();
                     });
 
+                    let mut custom = config.custom_basis.is_some();
+                    if ui.checkbox(&mut custom, "custom basis").changed() {
+                        config.custom_basis = custom.then_some(Mat3::IDENTITY);
+                    }
+                    if let Some(basis) = &mut config.bypass_change_detection().custom_basis
+                        && custom_basis_ui(ui, basis, basis_euler)
+                    {
+                        config.set_changed();
+                    }
+
                     ui.horizontal(|ui| {
                         ui.label("pos mode");
                         if ui
@@ -165,6 +267,252 @@ pub fn system_settings_ui(
                             config.set_changed();
                         }
                     });
+
+                    if quat_sign_ui(ui, &mut config.bypass_change_detection().quat_sign) {
+                        config.set_changed();
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("euler range");
+                        for range in [AngleRange::Signed, AngleRange::Positive] {
+                            if ui.selectable_label(config.euler_range == range, range.name()).clicked() {
+                                config.euler_range = range;
+                            }
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut animation.enabled, "animate changes");
+                        ui.add_enabled(
+                            animation.enabled,
+                            egui::Checkbox::new(&mut animation.show_mirror_plane, "mirror plane"),
+                        );
+                    });
+
+                    ui.collapsing("Other Coordinate Systems", |ui| {
+                        if extra_coordinates_ui(ui, extra_coords.bypass_change_detection()) {
+                            extra_coords.set_changed();
+                        }
+                    });
                 });
             });
 }
+
+/// Basis relative to the preset, entered directly or as a rotation of the preset
+fn custom_basis_ui(ui: &mut egui::Ui, basis: &mut Mat3, euler: &mut Vec3) -> bool {
+    let mut changed = false;
+
+    egui::Grid::new("custom_basis").num_columns(3).show(ui, |ui| {
+        ui.colored_label(egui::Color32::RED, "X");
+        ui.colored_label(egui::Color32::GREEN, "Y");
+        ui.colored_label(egui::Color32::from_rgb(125, 125, 255), "Z");
+        ui.end_row();
+
+        for row in 0..3 {
+            for col in 0..3 {
+                let widget = egui::DragValue::new(&mut basis.col_mut(col)[row]).speed(SCROLL_SPEED_POS);
+                changed |= ui.add(widget).changed();
+            }
+            ui.end_row();
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("rotation (XYZ)");
+        for value in euler.as_mut() {
+            ui.add(egui::DragValue::new(value).speed(SCROLL_SPEED_DEG));
+        }
+        if ui.button("Set").clicked() {
+            let Vec3 { x, y, z } = euler.map(f32::to_radians);
+            *basis = Mat3::from_euler(EulerRot::XYZ, x, y, z);
+            changed = true;
+        }
+    });
+
+    let check = crate::rotation::check_matrix(*basis);
+    if !check.is_orthonormal() {
+        ui.colored_label(
+            ui.visuals().error_fg_color,
+            format!("Not orthonormal (error {:.4}), the preset is used instead", check.orthonormality_error),
+        );
+        if ui.button("Orthonormalize").clicked()
+            && let Some(mut orthonormalized) = crate::rotation::orthonormalize_gram_schmidt(*basis)
+        {
+            // handedness of the basis is kept
+            if check.determinant < 0.0 {
+                orthonormalized.z_axis = -orthonormalized.z_axis;
+            }
+            *basis = orthonormalized;
+            changed = true;
+        }
+    } else if check.determinant < 0.0 {
+        ui.label("The basis flips handedness");
+    }
+
+    changed
+}
+
+/// Selects the conventions the model was authored in, so that it can be converted to match the object
+fn model_conventions_ui(ui: &mut egui::Ui, index: usize, model: &mut Model) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        changed = conventions_ui(
+            ui,
+            ("model", index),
+            &mut model.up,
+            &mut model.up_sign,
+            &mut model.forward,
+            &mut model.forward_sign,
+            &mut model.hand,
+        );
+    });
+    changed
+}
+
+/// Up and forward axes with their signs and the hand, in a single row
+fn conventions_ui(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash + Copy,
+    up: &mut Axis,
+    up_sign: &mut f32,
+    forward: &mut Axis,
+    forward_sign: &mut f32,
+    hand: &mut Hand,
+) -> bool {
+    let signed_axis_ui = |ui: &mut egui::Ui, salt: &str, axis: &mut Axis, sign: &mut f32| {
+        let mut changed = false;
+        let name = |axis: Axis, sign: f32| format!("{}{}", if sign < 0.0 { "-" } else { "+" }, axis.name());
+        egui::ComboBox::from_id_salt((salt, id))
+            .selected_text(name(*axis, *sign))
+            .width(50.0)
+            .show_ui(ui, |ui| {
+                for option in Axis::all() {
+                    for option_sign in [1.0, -1.0] {
+                        let selected = *axis == option && *sign == option_sign;
+                        if ui.selectable_label(selected, name(option, option_sign)).clicked() {
+                            *axis = option;
+                            *sign = option_sign;
+                            changed = true;
+                        }
+                    }
+                }
+            });
+        changed
+    };
+
+    let mut changed = false;
+    ui.label("Up");
+    if signed_axis_ui(ui, "up", up, up_sign) {
+        if *up == *forward {
+            *forward = Axis::all().into_iter().find(|&axis| axis != *up).unwrap();
+        }
+        changed = true;
+    }
+    ui.label("Fw");
+    if signed_axis_ui(ui, "forward", forward, forward_sign) {
+        if *up == *forward {
+            *up = Axis::all().into_iter().find(|&axis| axis != *forward).unwrap();
+        }
+        changed = true;
+    }
+    changed |= ui.selectable_value(hand, Hand::Left, "L").changed();
+    changed |= ui.selectable_value(hand, Hand::Right, "R").changed();
+
+    changed
+}
+
+/// Sign rule for displayed quaternions, the reference is entered as WXYZ
+fn quat_sign_ui(ui: &mut egui::Ui, sign: &mut QuatSign) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("quat sign");
+        egui::ComboBox::from_id_salt("quat_sign")
+            .selected_text(sign.name())
+            .show_ui(ui, |ui| {
+                let reference = match *sign {
+                    QuatSign::Reference(reference) => reference,
+                    _ => Quat::IDENTITY,
+                };
+                for option in [QuatSign::AsComputed, QuatSign::PositiveW, QuatSign::Continuous, QuatSign::Reference(reference)] {
+                    changed |= ui.selectable_value(sign, option, option.name()).changed();
+                }
+            });
+    });
+
+    if let QuatSign::Reference(reference) = sign {
+        ui.horizontal(|ui| {
+            ui.label("reference");
+            let mut wxyz = [reference.w, reference.x, reference.y, reference.z];
+            for value in &mut wxyz {
+                changed |= ui.add(egui::DragValue::new(value).speed(SCROLL_SPEED_POS)).changed();
+            }
+            let [w, x, y, z] = wxyz;
+            *reference = Quat::from_xyzw(x, y, z, w);
+        });
+    }
+
+    changed
+}
+
+/// Coordinate systems that values are displayed in next to the main one
+fn extra_coordinates_ui(ui: &mut egui::Ui, extra: &mut ExtraCoordinateSystems) -> bool {
+    let mut changed = false;
+    let mut removed = None;
+    let names: Vec<_> = extra.0.iter().map(|coords| coords.name.clone()).collect();
+
+    for (i, coords) in extra.0.iter_mut().enumerate() {
+        ui.separator();
+        ui.horizontal(|ui| {
+            // names identify the systems in copied objects, so they are kept unique
+            let mut name = coords.name.clone();
+            let response = ui.add(egui::TextEdit::singleline(&mut name).desired_width(100.0));
+            if response.changed() && !names.contains(&name) {
+                coords.name = name;
+                changed = true;
+            }
+            if ui.button("Delete").clicked() {
+                removed = Some(i);
+            }
+        });
+        ui.horizontal(|ui| {
+            changed |= conventions_ui(
+                ui,
+                ("extra_coords", i),
+                &mut coords.up,
+                &mut coords.up_sign,
+                &mut coords.forward,
+                &mut coords.forward_sign,
+                &mut coords.hand,
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("pos mode");
+            changed |= ui.selectable_value(&mut coords.position_mode, PositionMode::Flat, "flat").changed();
+            changed |= ui.selectable_value(&mut coords.position_mode, PositionMode::Rotated, "rotated").changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("pos scale");
+            let widget = egui::DragValue::new(&mut coords.positions_scale)
+                .range(0.00001..=f32::INFINITY)
+                .speed(SCROLL_SPEED_SCALE);
+            changed |= ui.add(widget).changed();
+        });
+    }
+
+    if let Some(i) = removed {
+        extra.0.remove(i);
+        changed = true;
+    }
+
+    ui.separator();
+    if ui.button("Add").clicked() {
+        let name = (1..)
+            .map(|i| format!("System {i}"))
+            .find(|name| extra.find_name(name).is_none())
+            .unwrap();
+        extra.0.push(NamedCoordinates::new(name));
+        changed = true;
+    }
+
+    changed
+}